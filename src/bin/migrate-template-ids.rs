@@ -0,0 +1,93 @@
+// Run this once: reads a status.bincode from back when collections pointed at
+// templates by their index in a Vec, and writes the status.json the bot reads
+// now, where they point at stable template IDs instead
+
+extern crate madlibs;
+extern crate bincode;
+extern crate chrono;
+#[macro_use]
+extern crate serde_derive;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+
+use madlibs::AccountID;
+use madlibs::bot::{self, BotStatus};
+use madlibs::collection::{CollectionStatus, Response};
+use madlibs::library::TemplateID;
+use madlibs::pos::POS;
+use madlibs::template::{Template, Token};
+
+// These are copied from the old schema, don't "fix" them
+
+#[derive(Deserialize)]
+struct OldToken {
+    text: Option<String>,
+    is_placeholder: bool,
+    pos: Option<POS>,
+}
+
+#[derive(Deserialize)]
+struct OldTemplate {
+    title: Option<String>,
+    body: Vec<OldToken>,
+}
+
+#[derive(Deserialize)]
+struct OldCollectionStatus {
+    template_id: usize,
+    participants: HashSet<AccountID>,
+    resolved: Vec<(POS, String)>,
+}
+
+#[derive(Deserialize)]
+struct OldBotStatus {
+    last_noti_date: chrono::DateTime<chrono::Utc>,
+    known_templates: Vec<OldTemplate>,
+    collection_toots: HashMap<String, OldCollectionStatus>,
+}
+
+fn main() {
+    let file = File::open("status.bincode").expect("couldn't open status.bincode");
+    let old: OldBotStatus = bincode::deserialize_from(file).expect("couldn't parse status.bincode");
+
+    let mut new = BotStatus::new();
+    new.last_noti_date = old.last_noti_date;
+    // Maps old index -> new ID. Duplicates all end up pointing at the first
+    // copy, since the old Vec kept every one of them
+    let mut ids: Vec<TemplateID> = Vec::new();
+    for template in old.known_templates {
+        let body = template.body.into_iter().map(|token| Token {
+            text: token.text,
+            is_placeholder: token.is_placeholder,
+            pos: token.pos,
+            hint: None,
+        }).collect();
+        ids.push(new.known_templates.submit(Template::new(template.title, body)).id());
+    }
+    for (toot_id, old_collection) in old.collection_toots {
+        let template_id = match ids.get(old_collection.template_id) {
+            Some(id) => *id,
+            None => {
+                println!("dropping collection {}, its template {} doesn't exist",
+                         toot_id, old_collection.template_id);
+                continue;
+            }
+        };
+        let mut participants = old_collection.participants.into_iter();
+        let submitter = participants.next().expect("collection without a submitter");
+        let mut collection = CollectionStatus::new(template_id, submitter);
+        for participant in participants {
+            collection.add_participant(participant);
+        }
+        // we never knew who sent which word
+        collection.add_responses(&String::new(), None, old_collection.resolved.into_iter()
+            .map(|(pos, word)| Response::new(pos, &word))
+            .collect());
+        new.collection_toots.insert(toot_id, collection);
+    }
+
+    bot::save_status(&new);
+    println!("migrated {} templates and {} collections, you can delete status.bincode now",
+             new.known_templates.len(), new.collection_toots.len());
+}
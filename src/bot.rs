@@ -1,93 +1,246 @@
 // Deals with the botty aspects: polling, sending, etc
 
 use elefren::{Mastodon, MastodonClient, Registration, StatusBuilder, entities::status::Status};
+use elefren::status_builder::Visibility;
 use elefren::helpers::cli;
 use elefren::helpers;
 use elefren::entities::*;
 use rand::Rng;
+use chrono::Timelike;
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::collections::HashSet;
 use std::collections::HashMap;
 
 use collection;
+use command;
+use template;
+use stats::{self, Post, Engagement, PlayerTotals};
 use pos;
 
-use template::Template;
-use collection::CollectionStatus;
+use template::{Template, Mode};
+use library::{Library, Submitted, TemplateID};
+use collection::{CollectionStatus, Selection, Limits};
+use config::{Config, Acknowledge, Credit, Roundup};
+use filter::Filter;
+use scheduler::{Scheduler, Action};
 use AccountID;
 
 const MAX_STATUS_LENGTH: usize = 512;
 const MAX_TEMPLATE_LENGTH: usize = 4096;
+// most posts check_engagement looks up each time around
+const ENGAGEMENT_CHECKS: usize = 5;
+// times around the poll loop a pending action gets before we give up on it
+const PENDING_TRIES: u32 = 5;
+
+const STATUS_FILE: &str = "status.json";
+// from before template IDs, needs bin/migrate-template-ids to be read
+const LEGACY_STATUS_FILE: &str = "status.bincode";
+// one line per admin action, never truncated
+const AUDIT_FILE: &str = "audit.log";
 
 // elefren continues to use String in future versions so this is future-aware
-type StatusID = String;
+pub type StatusID = String;
 
 #[derive(Deserialize, Serialize)]
 pub struct BotStatus {
     pub last_noti_date: chrono::DateTime<chrono::Utc>,
-    pub known_templates: Vec<Template>,
+    pub known_templates: Library,
     pub collection_toots: HashMap<StatusID, CollectionStatus>,
+    // things commands asked for that need posting, done next time around the
+    // poll loop
+    #[serde(default)]
+    pub pending: Vec<PendingAction>,
+    // how many times the first of those has failed
+    #[serde(default)]
+    pub pending_failures: u32,
+    // accounts whose mentions and toots we pretend not to see
+    #[serde(default)]
+    pub blocked: HashSet<AccountID>,
+    // no random posts while this is set
+    #[serde(default)]
+    pub paused: bool,
+    // false for people who'd rather we didn't mention them, see
+    // CollectionStatus for the same thing for one game
+    #[serde(default)]
+    pub mention_prefs: HashMap<AccountID, bool>,
+    // when the next random post goes out, so restarting doesn't change it
+    #[serde(default)]
+    pub next_random: Option<chrono::DateTime<chrono::Utc>>,
+    // the finished madlibs we've posted lately and how they went
+    #[serde(default)]
+    pub posts: HashMap<StatusID, Post>,
+    // what the older ones got each player, see stats::prune
+    #[serde(default)]
+    pub player_totals: HashMap<AccountID, PlayerTotals>,
+    // when the last roundup went out, or when we started counting for the
+    // first one
+    #[serde(default)]
+    pub last_roundup: Option<chrono::DateTime<chrono::Utc>>,
+    // people who'd rather not be on the leaderboard
+    #[serde(default)]
+    pub off_leaderboard: HashSet<AccountID>,
+    // the day (in the schedule's timezone) of the last template of the day
+    #[serde(default)]
+    pub last_daily: Option<chrono::NaiveDate>,
+}
+impl BotStatus {
+    pub fn new() -> Self {
+        BotStatus {
+            // WARNING: Don't use this bot in the past
+            last_noti_date: chrono::DateTime::from_utc(
+                                chrono::naive::NaiveDateTime::from_timestamp(0, 0),
+                                chrono::Utc),
+            known_templates: Library::new(),
+            collection_toots: HashMap::new(),
+            pending: Vec::new(),
+            pending_failures: 0,
+            blocked: HashSet::new(),
+            paused: false,
+            mention_prefs: HashMap::new(),
+            next_random: None,
+            posts: HashMap::new(),
+            player_totals: HashMap::new(),
+            last_roundup: None,
+            off_leaderboard: HashSet::new(),
+            last_daily: None,
+        }
+    }
+    // so its favourites and boosts get kept track of, see check_engagement
+    pub fn record_post(&mut self, first_toot: Option<StatusID>, post: Post) {
+        if let Some(toot_id) = first_toot {
+            self.posts.insert(toot_id, post);
+        }
+    }
+    pub fn wants_mention(&self, acct: &AccountID) -> bool {
+        *self.mention_prefs.get(acct).unwrap_or(&true)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum PendingAction {
+    Post(TemplateID),
+    // finish the game with whatever the timeline can fill in
+    Close(StatusID),
+    Cancel(StatusID),
 }
 
 type BotError = elefren::errors::Error;
 type Result<T> = std::result::Result<T, BotError>;
 
-fn solve_and_post(mastodon: &Mastodon, template: &mut Template, used_statuses: &mut HashSet<String>, acct: Option<String>) -> Result<()> {
+// Fills in the template from the home timeline, one word per toot
+// returns None if we ran out of toots first
+fn solve(mastodon: &Mastodon, template: &mut Template, used_statuses: &mut HashSet<String>, blocked: &HashSet<AccountID>, filter: &Filter) -> Result<Option<String>> {
+    // it might not have needed anything from us at all
+    if let Some(text) = template.check_done() {
+        return Ok(Some(text));
+    }
     let home = mastodon.get_home_timeline()?;
     for status in home.items_iter() {
         if status.account.acct == "madlibs"
             || status.content.contains("madlibs")
+            || blocked.contains(&status.account.acct)
             || used_statuses.contains(&status.id) {
             continue
         }
         used_statuses.insert(status.id);
-        match template.reduce(&status.content) {
-            Some(text) => {
-                let end = match acct {
-                    Some(acct) => format!("cc @{}", acct),
-                    None => String::new()
-                };
-                post_vec(mastodon, &split(&text, &end), None)?;
-                break
-            },
-            None => ()
+        if let Some(text) = template.reduce(&status.content, filter) {
+            return Ok(Some(text));
+        }
+    }
+    Ok(None)
+}
+
+// The ID of the first toot, if there was enough to post
+fn solve_and_post(mastodon: &Mastodon, template: &mut Template, used_statuses: &mut HashSet<String>, blocked: &HashSet<AccountID>, filter: &Filter, acct: Option<String>) -> Result<Option<StatusID>> {
+    if let Some(text) = solve(mastodon, template, used_statuses, blocked, filter)? {
+        let end = match acct {
+            Some(acct) => format!("cc @{}", acct),
+            None => String::new()
         };
+        return post_vec(mastodon, &split(&text, &end), None, None);
     }
-    Ok(())
+    Ok(None)
+}
+
+// Every blank on its own line, numbered, with its hint
+fn format_blanks(template: &Template) -> String {
+    let mut text = String::new();
+    for (i, blank) in template.blanks().iter().enumerate() {
+        text.push_str(&format!("\n{}. {}", i + 1, pos::pos_to_str(&blank.pos.unwrap())));
+        if let Some(hint) = &blank.hint {
+            text.push_str(&format!(" ({})", hint));
+        }
+    }
+    text
 }
 
-fn format_collection_toot(template: &Template, acct: Option<AccountID>) -> String {
+// intro is what it opens with, like "let's play madlibs!"
+// Lists every blank if that fits in one toot, otherwise just how many of each
+// POS it needs, which is short no matter how many blanks there are
+fn format_collection_toot(template: &Template, acct: Option<AccountID>, intro: &str, config: &Config) -> String {
+    let text = format_collection_toot_with(template, acct.as_ref(), intro, config, &format_blanks(template));
+    if text.len() <= MAX_STATUS_LENGTH {
+        return text;
+    }
+    let counts = format!("\n{}", pos::format_requirements(&template.requirements()));
+    format_collection_toot_with(template, acct.as_ref(), intro, config, &counts)
+}
+
+fn format_collection_toot_with(template: &Template, acct: Option<&AccountID>, intro: &str, config: &Config, blanks: &str) -> String {
     let title = match &template.title {
         Some(title) => title,
         None => "Untitled",
     };
-    let mut text = format!("let's play madlibs! this one's called: **{}**
+    let mut text = format!("{} this one's called: **{}**
 
 i need the following words:
-", title);
-    for (pos, count) in template.requirements() {
-        text.push_str(&format!("\n{}x: {}", count, pos::pos_to_str(&pos)));
-    }
+{}", intro, title, blanks);
     text.push_str("
 
 contribute one or more words by replying like this:
 noun: hegemony
-verbs: sucks");
+verbs: sucks
+
+or pick which blank they go in with 2: hegemony or noun#1: hegemony");
+    if let Some(rules) = config.game_limits(template).describe() {
+        text.push_str(&format!("\n\nrules: {}", rules));
+    }
+    let hours = config.game_length(template.mode()).num_hours();
+    if config.selection == Selection::Votes {
+        text.push_str(&format!("\n\nfavourite the replies you like best, the words with \
+                                the most favourites win when i wrap it up in {} hours",
+                               hours));
+    } else if template.mode() == Mode::Hybrid {
+        text.push_str(&format!("\n\nin {} hours i'll fill in whatever's left from my timeline", hours));
+    } else {
+        text.push_str(&format!("\n\ni'll wrap it up in {} hours, ready or not", hours));
+    }
     if let Some(acct) = acct {
         text.push_str(&format!("\n\ncc @{}", acct));
     }
     text
 }
 
-fn post_collection(mastodon: &Mastodon, template: &Template, acct: Option<AccountID>) -> Result<StatusID> {
-    Ok(mastodon.new_status(StatusBuilder::new()
-        .status(format_collection_toot(template, acct))
-        .build()?
-    )?.id)
+// The game is the first toot, only a really long title needs more than one
+fn post_collection(mastodon: &Mastodon, template: &Template, acct: Option<AccountID>, intro: &str, config: &Config) -> Result<StatusID> {
+    let text = format_collection_toot(template, acct, intro, config);
+    let first = post_vec(mastodon, &split(&text, ""), None, None)?;
+    // split always has at least one
+    Ok(first.unwrap())
+}
+
+// Starts keeping track of a game once its toot is up
+fn open_collection(bot_status: &mut BotStatus, toot_id: StatusID, mut collection: CollectionStatus, mode: Mode, limits: Limits, config: &Config) {
+    collection.set_selection(config.selection);
+    collection.set_limits(limits);
+    collection.set_mode(mode);
+    collection.set_deadline(chrono::Utc::now() + config.game_length(mode));
+    bot_status.collection_toots.insert(toot_id, collection);
 }
 
-fn process_template_mention(mastodon: &Mastodon, notification: notification::Notification, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>) -> Result<()> {
+fn process_template_mention(mastodon: &Mastodon, notification: notification::Notification, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config) -> Result<()> {
     info!("... it was a non-collection mention");
     let status = notification.status.unwrap();
     let acct = notification.account.acct;
@@ -106,19 +259,57 @@ fn process_template_mention(mastodon: &Mastodon, notification: notification::Not
             return Ok(());
         }
     };
+    template.author = Some(acct.clone());
+    template.strictness = config.default_strictness;
+    template.created = Some(status.created_at);
+    template.language = status.language.clone();
     // Ignore mentions that don't include any template words
     if template.body.len() > 1 {
         info!("... with a valid template");
-        if template.title.is_some() {
-            info!("... and a title (manual mode)");
-            let toot_id = post_collection(mastodon, &template, Some(acct.clone()))?;
-            // hasn't been inserted yet so no -1
-            let plate_id = bot_status.known_templates.len();
-            bot_status.collection_toots.insert(toot_id, CollectionStatus::new(plate_id, acct));
+        if !config.filter.allows(&template.source()) {
+            template.held = true;
+            // not submit, we don't want it merged into something that's fine
+            let id = bot_status.known_templates.add(template);
+            warn!("... which tripped the filter, holding it as #{}", id);
+            reply(mastodon, &status, &format!(
+                "your template has a word i'm not allowed to post, so it's held \
+                 until an admin looks at it (#{})", id))?;
+            return Ok(());
+        }
+        // Solve and post changes the template, so the library gets a clean one
+        let submitted = bot_status.known_templates.submit(template.clone());
+        match submitted {
+            Submitted::New(id) => info!("... which is new, #{}", id),
+            Submitted::Duplicate(id) => info!("... which we already know as #{}", id),
+            Submitted::NearDuplicate(id) => info!("... which is close enough to #{}", id),
+        }
+        let plate_id = submitted.id();
+        let too_many_games = bot_status.collection_toots.len() >= config.max_open_games;
+        let mode = template.mode();
+        if mode != Mode::Automatic && too_many_games {
+            info!("... in {} mode, but there are too many open games, so automatic", mode.name());
+        }
+        if mode != Mode::Automatic && !too_many_games {
+            info!("... in {} mode", mode.name());
+            let cc = Some(acct.clone()).filter(|acct| bot_status.wants_mention(acct));
+            // for a (near) duplicate, the game fills in the one we already had,
+            // so that's what goes in the toot, played how they asked for
+            let mut game_template = bot_status.known_templates.get(plate_id).unwrap().clone();
+            game_template.mode = Some(mode);
+            game_template.limits = template.limits;
+            let toot_id = post_collection(mastodon, &game_template, cc, "let's play madlibs!", config)?;
+            let collection = CollectionStatus::new(plate_id, acct);
+            open_collection(bot_status, toot_id, collection, mode, config.game_limits(&game_template), config);
+            bot_status.known_templates.mark_posted(plate_id, chrono::Utc::now());
         } else {
-            solve_and_post(mastodon, &mut template, used_statuses, Some(acct))?;
+            let cc = Some(acct).filter(|acct| bot_status.wants_mention(acct));
+            let first = solve_and_post(mastodon, &mut template, used_statuses, &bot_status.blocked, &config.filter, cc)?;
+            // the timeline might not have had the words for it
+            if first.is_some() {
+                bot_status.known_templates.mark_posted(plate_id, chrono::Utc::now());
+            }
+            bot_status.record_post(first, Post::new(plate_id, None, Vec::new(), chrono::Utc::now()));
         }
-        bot_status.known_templates.push(template);
     }
     Ok(())
 }
@@ -182,21 +373,182 @@ fn split(mut text: &str, append: &str) -> Vec<String> {
     posts
 }
 
-fn post_vec(mastodon: &Mastodon, posts: &[String], mut reply_id: Option<String>) -> Result<()> {
+// visibility None is whatever the account's default is
+// Returns the ID of the first one
+fn post_vec(mastodon: &Mastodon, posts: &[String], mut reply_id: Option<String>, visibility: Option<Visibility>) -> Result<Option<StatusID>> {
+    let mut first = None;
     for post in posts {
         let mut partial = StatusBuilder::new();
-        let status = if let Some(id) = reply_id {
-            partial.in_reply_to(id)
-        } else { &mut partial }
-            .status(post)
-            .build()?;
-        reply_id = Some(mastodon.new_status(status)?.id);
+        partial.status(post.as_str());
+        if let Some(id) = reply_id {
+            partial.in_reply_to(id);
+        }
+        if let Some(visibility) = visibility {
+            partial.visibility(visibility);
+        }
+        let id = mastodon.new_status(partial.build()?)?.id;
+        first = first.or_else(|| Some(id.clone()));
+        reply_id = Some(id);
+    }
+    Ok(first)
+}
+
+// Works out who to credit for a finished game, and bolds their words if the
+// credits go in the same post. Call it before anything else fills blanks in
+fn apply_credit<F>(template: &mut Template, credits: &[Option<AccountID>], config: &Config, mention: F) -> Option<String>
+        where F: Fn(&AccountID) -> bool {
+    if config.credit == Credit::Off {
+        return None;
+    }
+    let list = collection::format_credits(template, credits, mention);
+    if config.credit == Credit::Post {
+        collection::bold_credited(template, credits);
+    }
+    list
+}
+
+// everyone credited with a word, once each
+fn contributors(credits: &[Option<AccountID>]) -> Vec<AccountID> {
+    let mut accts: Vec<AccountID> = credits.iter().filter_map(|acct| acct.clone()).collect();
+    accts.sort();
+    accts.dedup();
+    accts
+}
+
+// "\ncc @a @b" for the first max accounts, to go under a post, and a "cc"
+// toot of its own for each max after that
+fn format_ccs(accts: &[&AccountID], max: usize) -> (String, Vec<String>) {
+    let mut lines = accts.chunks(max.max(1)).map(|chunk| {
+        let ats: Vec<String> = chunk.iter().map(|acct| format!("@{}", acct)).collect();
+        format!("cc {}", ats.join(" "))
+    });
+    let first = lines.next().map_or(String::new(), |line| format!("\n{}", line));
+    (first, lines.collect())
+}
+
+// The posts for a finished game: the credits go under the result if there's
+// room and that's what the config wants, otherwise in a reply of their own
+fn finished_posts(text: &str, ccs: &str, credits: Option<String>, credit: Credit) -> Vec<String> {
+    let credits = match credits {
+        Some(credits) => credits,
+        None => return split(text, ccs),
+    };
+    let together = format!("{}\n\n{}{}", text, credits, ccs);
+    if credit == Credit::Post && together.len() <= MAX_STATUS_LENGTH {
+        return vec![together];
+    }
+    let mut posts = split(text, ccs);
+    posts.append(&mut split(&format!("who sent what:\n\n{}", credits), ""));
+    posts
+}
+
+// same visibility as what we're replying to, so DMs stay DMs
+fn reply(mastodon: &Mastodon, status: &Status, text: &str) -> Result<()> {
+    reply_as(mastodon, status, text, status.visibility)
+}
+
+fn reply_as(mastodon: &Mastodon, status: &Status, text: &str, visibility: Visibility) -> Result<()> {
+    let text = format!("@{} {}", status.account.acct, text);
+    post_vec(mastodon, &split(&text, ""), Some(status.id.clone()), Some(visibility))?;
+    Ok(())
+}
+
+fn audit(admin: &AccountID, command: &command::Command, result: &str) {
+    warn!("admin {} did {:?}: {}", admin, command, result);
+    let line = format!("{}\t{}\t{:?}\t{}\n", chrono::Utc::now().to_rfc3339(), admin, command, result);
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(AUDIT_FILE)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(e) = written {
+        error!("couldn't write to {}: {}", AUDIT_FILE, e);
+    }
+}
+
+// returns true if this was a !command (even a broken one), false if it wasn't
+fn process_command_mention(mastodon: &Mastodon, notification: &notification::Notification, bot_status: &mut BotStatus, config: &Config) -> Result<bool> {
+    let status = notification.status.as_ref().unwrap();
+    let command = match command::parse_command(&status.content, &config.filter) {
+        Some(Ok(command)) => command,
+        Some(Err(e)) => {
+            toot_parse_error(mastodon, status, e, "command")?;
+            return Ok(true);
+        }
+        None => return Ok(false),
+    };
+    info!("... it was a command: {:?}", command);
+    let acct = &notification.account.acct;
+    let is_admin = config.is_admin(acct);
+    let visibility = if command.shows_held(is_admin, bot_status) {
+        Visibility::Direct
+    } else {
+        status.visibility
+    };
+    let text = command.execute(acct, is_admin, status.in_reply_to_id.as_ref(), bot_status);
+    if is_admin && command.is_audited() {
+        audit(acct, &command, &text);
+    }
+    reply_as(mastodon, status, &text, visibility)?;
+    Ok(true)
+}
+
+// The instance doesn't know the status anymore, it was deleted. Anything else
+// (being rate limited, the instance being down) is no reason to forget it
+fn is_deleted(e: &BotError) -> bool {
+    match e {
+        BotError::Api(api) => api.error.as_ref().map(String::as_str) == Some("Record not found"),
+        _ => false,
+    }
+}
+
+// Catches a game up with replies that were edited or deleted since we took
+// words from them, and how many votes they have. We don't get told about any
+// of that, so we ask for each one again
+fn refresh_responses(mastodon: &Mastodon, collection: &mut CollectionStatus, templates: &Library, filter: &Filter) -> Result<()> {
+    let template = match templates.get(collection.template_id()) {
+        Some(template) => template,
+        None => return Ok(()),
+    };
+    for (status_id, author) in collection.response_statuses() {
+        match mastodon.get_status(&status_id) {
+            Ok(status) => {
+                // if the edit doesn't parse, the words from before stand
+                let placed = collection::parse_response(&status.content, filter)
+                    .and_then(|answers| collection::place(template, answers));
+                if let Ok(resp) = placed {
+                    let (resp, _) = collection::tagger_check(resp, template.strictness, template::tag_word);
+                    collection.remove_status(&status_id);
+                    // an edit can't sneak in words the game has no blank for,
+                    // or get around the limits
+                    let kept = match collection.check_responses(templates, resp) {
+                        Some(mut check) => {
+                            collection.limit_responses(&author, &mut check);
+                            check.kept()
+                        },
+                        None => Vec::new(),
+                    };
+                    if kept.is_empty() {
+                        collection.reject_status(&status_id, &author);
+                    }
+                    collection.add_responses(&author, Some(&status_id), kept);
+                }
+                // our own favourite from acknowledging it doesn't count
+                let ours = if status.favourited == Some(true) { 1 } else { 0 };
+                collection.set_votes(&status_id, status.favourites_count.saturating_sub(ours));
+            },
+            Err(ref e) if is_deleted(e) => {
+                info!("response {} is gone, dropping its words", status_id);
+                collection.remove_status(&status_id);
+            },
+            Err(e) => return Err(e),
+        }
     }
     Ok(())
 }
 
 // returns true if this WAS a valid, live collection mention, false if it wasn't
-fn process_collection_mention(mastodon: &Mastodon, notification: &notification::Notification, bot_status: &mut BotStatus) -> Result<bool> {
+fn process_collection_mention(mastodon: &Mastodon, notification: &notification::Notification, bot_status: &mut BotStatus, config: &Config) -> Result<bool> {
     let status = notification.status.as_ref().unwrap();
     // we can't chain if-let, (feature(let_chains) doesn't even work),
     // returns will do the trick
@@ -204,21 +556,104 @@ fn process_collection_mention(mastodon: &Mastodon, notification: &notification::
         if let Some(collection) = bot_status.collection_toots.get_mut(reply_id) {
             info!("... it was a collection mention");
             let status = notification.status.as_ref().unwrap();
-            let resp = match collection::parse_response(&status.content) {
+            let template = match bot_status.known_templates.get(collection.template_id()) {
+                Some(template) => template,
+                // its template was deleted, the game can't go anywhere
+                None => return Ok(true),
+            };
+            let placed = collection::parse_response(&status.content, &config.filter)
+                .and_then(|answers| collection::place(template, answers));
+            let resp = match placed {
                 Ok(resp) => resp,
                 Err(e) => {
+                    // they might fix it with an edit
+                    collection.reject_status(&status.id, &notification.account.acct);
                     toot_parse_error(mastodon, status, e, "response")?;
                     return Ok(true);
                 }
             };
-            collection.add_responses(resp);
-            collection.add_participant(notification.account.acct.clone());
-            match collection.check_done(&bot_status.known_templates) {
-                Some(text) => {
-                    let ccs = collection.get_participant_ats();
-                    post_vec(mastodon, &split(&text, &ccs), Some(reply_id.clone()))?;
+            let acct = &notification.account.acct;
+            let now = chrono::Utc::now();
+            // only if it had words in it, they can still chat while they wait
+            if !resp.is_empty() {
+                if let Some(wait) = collection.wait_left(acct, now) {
+                    reply(mastodon, status, &format!(
+                        "you can send more words to this one in about {} minute(s), \
+                         reply again then", wait.num_minutes() + 1))?;
+                    return Ok(true);
+                }
+            }
+            let had_words = !resp.is_empty();
+            let strictness = collection.strictness(&bot_status.known_templates);
+            let (resp, complaint) = collection::tagger_check(resp, strictness, template::tag_word);
+            if let Some(complaint) = complaint {
+                reply(mastodon, status, &complaint)?;
+            }
+            let mut check = match collection.check_responses(&bot_status.known_templates, resp) {
+                Some(check) => check,
+                // its template was deleted, the game can't go anywhere
+                None => return Ok(true),
+            };
+            if let Some(limited) = collection.limit_responses(acct, &mut check) {
+                reply(mastodon, status, &limited)?;
+            }
+            let explanation = check.explain();
+            if let Some(explanation) = &explanation {
+                reply(mastodon, status, explanation)?;
+            }
+            // nothing to thank them for if it was all comments or unneeded
+            let accepted = !check.accepted.is_empty() || !check.surplus.is_empty();
+            if had_words && !accepted {
+                collection.reject_status(&status.id, acct);
+            }
+            collection.add_responses(acct, Some(&status.id), check.kept());
+            collection.add_participant(acct.clone());
+            if accepted {
+                collection.mark_sent(acct, now);
+            }
+            // voting games wait for their deadline, there's nothing to
+            // finish yet and favouriting would be voting
+            let voting = collection.selection() == Selection::Votes;
+            // make sure it's still done once we've caught up with edits
+            if !voting && collection.check_done(&bot_status.known_templates).is_some() {
+                refresh_responses(mastodon, collection, &bot_status.known_templates, &config.filter)?;
+            }
+            let done = if voting {
+                None
+            } else {
+                collection.credited(&bot_status.known_templates)
+                    .filter(|(template, _)| template.check_done().is_some())
+            };
+            match done {
+                Some((mut template, credits)) => {
+                    let prefs = &bot_status.mention_prefs;
+                    let post = Post::new(collection.template_id(), Some(reply_id.clone()),
+                                         contributors(&credits), chrono::Utc::now());
+                    let credits = apply_credit(&mut template, &credits, config,
+                                               |acct| collection.wants_mention(acct, prefs));
+                    let text = template.check_done().unwrap();
+                    let (ccs, more_ccs) = format_ccs(&collection.to_mention(prefs), config.max_mentions);
+                    let mut posts = finished_posts(&text, &ccs, credits, config.credit);
+                    posts.extend(more_ccs);
+                    let first = post_vec(mastodon, &posts, Some(reply_id.clone()), None)?;
+                    bot_status.collection_toots.remove(reply_id);
+                    bot_status.record_post(first, post);
                 }
                 // still waiting around
+                None if accepted => match config.acknowledge {
+                    Acknowledge::Favourite if voting => (),
+                    Acknowledge::Favourite => {
+                        mastodon.favourite(&status.id)?;
+                    },
+                    // they already got a reply
+                    Acknowledge::Reply if explanation.is_some() => (),
+                    Acknowledge::Reply => {
+                        let progress = collection.progress(&bot_status.known_templates)
+                            .unwrap_or_default();
+                        reply(mastodon, status, &format!("got it, thanks! {}", progress))?;
+                    },
+                    Acknowledge::Off => (),
+                },
                 None => (),
             }
             return Ok(true)
@@ -231,22 +666,336 @@ fn process_mention(
         mastodon: &Mastodon,
         notification: notification::Notification,
         bot_status: &mut BotStatus,
-        used_statuses: &mut HashSet<String>) -> Result<()> {
+        used_statuses: &mut HashSet<String>,
+        config: &Config) -> Result<()> {
     info!("mention from {}", &notification.account.acct);
-    if !process_collection_mention(mastodon, &notification, bot_status)? {
-        process_template_mention(mastodon, notification, bot_status, used_statuses)?;
+    // admins can't lock themselves out
+    if bot_status.blocked.contains(&notification.account.acct)
+        && !config.is_admin(&notification.account.acct) {
+        info!("... who is blocked, ignoring");
+        return Ok(());
+    }
+    if process_command_mention(mastodon, &notification, bot_status, config)? {
+        return Ok(());
+    }
+    if !process_collection_mention(mastodon, &notification, bot_status, config)? {
+        process_template_mention(mastodon, notification, bot_status, used_statuses, config)?;
+    }
+    Ok(())
+}
+
+// Whatever the scheduler picked, falling back to an automatic post when
+// there's nothing to bump or no template that can be a game
+fn scheduled_post<R: Rng>(mastodon: &Mastodon, scheduler: &mut Scheduler<R>, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config) -> Result<()> {
+    let open_games = bot_status.collection_toots.len();
+    match scheduler.next_action(open_games, config.max_open_games) {
+        Action::Bump => {
+            let oldest = bot_status.collection_toots.iter()
+                .min_by_key(|(_, collection)| collection.opened())
+                .map(|(game_id, _)| game_id.clone());
+            if let Some(game_id) = oldest {
+                return bump_collection(mastodon, &game_id, bot_status, config);
+            }
+        },
+        Action::NewGame => {
+            if let Some(id) = pick_game_template(&bot_status.known_templates, config) {
+                info!("opening a game with template #{}", id);
+                return open_library_game(mastodon, bot_status, id, "let's play madlibs!", config);
+            }
+        },
+        Action::Automatic => (),
+    }
+    post_random_madlib(mastodon, bot_status, used_statuses, config)
+}
+
+// Library::weights, but only the templates in the pools that are on, as long
+// as there are any
+fn pool_weights(templates: &Library, config: &Config, now: chrono::DateTime<chrono::Utc>) -> Vec<(TemplateID, f64)> {
+    let weights = templates.weights(now);
+    let tags = config.active_tags(now);
+    let pooled: Vec<(TemplateID, f64)> = weights.iter()
+        .filter(|(id, _)| tags.iter().any(|tag| templates.get(*id).unwrap().has_tag(tag)))
+        .cloned()
+        .collect();
+    if pooled.is_empty() {
+        weights
+    } else {
+        pooled
+    }
+}
+
+// A template for a game nobody asked for. untitled ones are fine, but not
+// ones people asked to be automatic
+fn pick_game_template(templates: &Library, config: &Config) -> Option<TemplateID> {
+    let weights: Vec<(TemplateID, f64)> = pool_weights(templates, config, chrono::Utc::now()).into_iter()
+        .filter(|(id, _)| templates.get(*id).unwrap().mode != Some(Mode::Automatic))
+        .collect();
+    stats::choose_weighted(&weights, &mut rand::thread_rng())
+}
+
+fn open_library_game(mastodon: &Mastodon, bot_status: &mut BotStatus, id: TemplateID, intro: &str, config: &Config) -> Result<()> {
+    let template = bot_status.known_templates.get(id).unwrap();
+    let mode = match template.mode {
+        Some(Mode::Hybrid) => Mode::Hybrid,
+        _ => Mode::Manual,
+    };
+    let toot_id = post_collection(mastodon, template, None, intro, config)?;
+    let limits = config.game_limits(template);
+    open_collection(bot_status, toot_id, CollectionStatus::for_template(id), mode, limits, config);
+    bot_status.known_templates.mark_posted(id, chrono::Utc::now());
+    Ok(())
+}
+
+// Opens the template of the day game, once a day after config.daily_hour
+fn post_daily(mastodon: &Mastodon, bot_status: &mut BotStatus, config: &Config) -> Result<()> {
+    let hour = match config.daily_hour {
+        Some(hour) => hour,
+        None => return Ok(()),
+    };
+    let local = config.schedule.local(chrono::Utc::now());
+    if local.hour() < hour || bot_status.last_daily == Some(local.date()) {
+        return Ok(());
     }
+    if bot_status.paused {
+        info!("skipping the template of the day, we're paused");
+    } else if bot_status.collection_toots.len() >= config.max_open_games {
+        info!("skipping the template of the day, there are enough games open");
+    } else if let Some(id) = pick_game_template(&bot_status.known_templates, config) {
+        info!("opening template #{} as the template of the day", id);
+        open_library_game(mastodon, bot_status, id, "it's time for the template of the day!", config)?;
+    }
+    bot_status.last_daily = Some(local.date());
     Ok(())
 }
 
-fn post_random_madlib(mastodon: &Mastodon, templates: &Vec<Template>, used_statuses: &mut HashSet<String>) -> Result<()> {
+fn post_random_madlib(mastodon: &Mastodon, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config) -> Result<()> {
     info!("posting random template");
+    let now = chrono::Utc::now();
+    let weights = pool_weights(&bot_status.known_templates, config, now);
+    let templates = &mut bot_status.known_templates;
+    let id = match stats::choose_weighted(&weights, &mut rand::thread_rng()) {
+        Some(id) => id,
+        None => {
+            warn!("... but we don't know any templates yet");
+            return Ok(());
+        }
+    };
     // Solve and post changes the template which we don't want, so we clone
-    let mut template = rand::thread_rng().choose(templates).unwrap().clone();
-    solve_and_post(mastodon, &mut template, used_statuses, None)?;
+    let mut template = templates.get(id).unwrap().clone();
+    let first = solve_and_post(mastodon, &mut template, used_statuses, &bot_status.blocked, &config.filter, None)?;
+    if first.is_some() {
+        templates.mark_posted(id, now);
+    }
+    bot_status.record_post(first, Post::new(id, None, Vec::new(), now));
+    Ok(())
+}
+
+// Finishes a game early, filling in whatever nobody sent from the timeline if
+// fill is set, otherwise only if it already has everything
+fn close_collection(mastodon: &Mastodon, game_id: &StatusID, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config, fill: bool) -> Result<()> {
+    let prefs = &bot_status.mention_prefs;
+    let collection = match bot_status.collection_toots.get_mut(game_id) {
+        Some(collection) => collection,
+        // closed some other way in the meantime
+        None => return Ok(()),
+    };
+    refresh_responses(mastodon, collection, &bot_status.known_templates, &config.filter)?;
+    let filled = collection.credited(&bot_status.known_templates);
+    let (ccs, more_ccs) = format_ccs(&collection.to_mention(prefs), config.max_mentions);
+    let mut post = Post::new(collection.template_id(), Some(game_id.clone()), Vec::new(), chrono::Utc::now());
+    let (text, credits) = match filled {
+        Some((mut template, credits)) => {
+            post.contributors = contributors(&credits);
+            // the timeline doesn't get credit, so this goes first
+            let credits = apply_credit(&mut template, &credits, config,
+                                       |acct| collection.wants_mention(acct, prefs));
+            let text = if fill {
+                solve(mastodon, &mut template, used_statuses, &bot_status.blocked, &config.filter)?
+            } else {
+                template.check_done()
+            };
+            (text, credits)
+        },
+        None => (None, None),
+    };
+    let finished = text.is_some();
+    let mut posts = match text {
+        Some(text) => finished_posts(&text, &ccs, credits, config.credit),
+        None => split("couldn't find enough words to finish this one, so it's closed unfinished", &ccs),
+    };
+    posts.extend(more_ccs);
+    let first = post_vec(mastodon, &posts, Some(game_id.clone()), None)?;
+    bot_status.collection_toots.remove(game_id);
+    if finished {
+        bot_status.record_post(first, post);
+    }
+    Ok(())
+}
+
+fn process_pending_action(mastodon: &Mastodon, action: &PendingAction, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config) -> Result<()> {
+    info!("doing pending {:?}", action);
+    match action {
+        PendingAction::Post(id) => {
+            if let Some(template) = bot_status.known_templates.get(*id) {
+                let now = chrono::Utc::now();
+                let first = solve_and_post(mastodon, &mut template.clone(), used_statuses, &bot_status.blocked, &config.filter, None)?;
+                if first.is_some() {
+                    bot_status.known_templates.mark_posted(*id, now);
+                }
+                bot_status.record_post(first, Post::new(*id, None, Vec::new(), now));
+            }
+        },
+        PendingAction::Close(game_id) => {
+            close_collection(mastodon, game_id, bot_status, used_statuses, config, true)?;
+        },
+        PendingAction::Cancel(game_id) => {
+            if bot_status.collection_toots.remove(game_id).is_some() {
+                post_vec(mastodon, &["this game was cancelled, sorry!".to_string()],
+                         Some(game_id.clone()), None)?;
+            }
+        },
+    }
+    Ok(())
+}
+
+fn close_expired_collections(mastodon: &Mastodon, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config) -> Result<()> {
+    let now = chrono::Utc::now();
+    let expired: Vec<StatusID> = bot_status.collection_toots.iter()
+        .filter(|(_, collection)| collection.is_expired(now))
+        .map(|(game_id, _)| game_id.clone())
+        .collect();
+    for game_id in expired {
+        info!("game {} ran out of time", game_id);
+        // hybrid games always get finished off from the timeline
+        let fill = config.fill_on_deadline
+            || bot_status.collection_toots[&game_id].mode() == Mode::Hybrid;
+        close_collection(mastodon, &game_id, bot_status, used_statuses, config, fill)?;
+    }
+    Ok(())
+}
+
+// Gives the oldest game that's due one a "still need" reply, and reboosts it
+// so it shows up on people's timelines again. One per call, so a bunch of
+// games don't all bump at once
+fn update_collections(mastodon: &Mastodon, bot_status: &mut BotStatus, config: &Config) -> Result<()> {
+    let now = chrono::Utc::now();
+    let oldest = bot_status.collection_toots.iter()
+        .filter(|(_, collection)| collection.needs_update(now, config.update_interval()))
+        .min_by_key(|(_, collection)| collection.opened())
+        .map(|(game_id, _)| game_id.clone());
+    match oldest {
+        Some(game_id) => bump_collection(mastodon, &game_id, bot_status, config),
+        None => Ok(()),
+    }
+}
+
+// Replies to a game with what it still needs and boosts it again
+fn bump_collection(mastodon: &Mastodon, game_id: &StatusID, bot_status: &mut BotStatus, config: &Config) -> Result<()> {
+    info!("bumping game {}", game_id);
+    let collection = bot_status.collection_toots.get_mut(game_id).unwrap();
+    refresh_responses(mastodon, collection, &bot_status.known_templates, &config.filter)?;
+    // before anything gets posted, so trying again can't post it twice
+    collection.mark_updated(chrono::Utc::now());
+    if let Some(progress) = collection.progress(&bot_status.known_templates) {
+        let text = format!("{}\n\nreply to the first toot in this thread to play!", progress);
+        post_vec(mastodon, &[text], Some(game_id.clone()), None)?;
+    }
+    // boosting again doesn't do anything unless we undo the old one first.
+    // the reply is already up, so this failing isn't worth trying it all again
+    let _ = mastodon.unreblog(game_id);
+    if let Err(e) = mastodon.reblog(game_id) {
+        warn!("couldn't boost game {} again: {}", game_id, e);
+    }
     Ok(())
 }
 
+// Looks at how the madlibs we posted are doing, a few at a time so a backlog
+// of them doesn't all get fetched at once
+fn check_engagement(mastodon: &Mastodon, bot_status: &mut BotStatus) -> Result<()> {
+    let now = chrono::Utc::now();
+    let due: Vec<StatusID> = bot_status.posts.iter()
+        .filter(|(_, post)| post.needs_check(now))
+        .map(|(toot_id, _)| toot_id.clone())
+        .take(ENGAGEMENT_CHECKS)
+        .collect();
+    for toot_id in due {
+        let status = match mastodon.get_status(&toot_id) {
+            Ok(status) => status,
+            // deleted, so there's nothing more to see, but what it got
+            // before still counts
+            Err(ref e) if is_deleted(e) => {
+                bot_status.posts.get_mut(&toot_id).unwrap().deleted = true;
+                continue;
+            },
+            Err(e) => return Err(e),
+        };
+        let post = bot_status.posts.get_mut(&toot_id).unwrap();
+        let engagement = Engagement {
+            favourites: status.favourites_count,
+            boosts: status.reblogs_count,
+            replies: status.replies_count.unwrap_or(0),
+        };
+        if let Some(template) = bot_status.known_templates.get_mut(post.template_id) {
+            template.stats.count(&post.engagement, &engagement);
+        }
+        post.engagement = engagement;
+        post.checked = Some(now);
+    }
+    Ok(())
+}
+
+// The best madlibs since the last roundup and who played them, when it's time
+fn post_roundup(mastodon: &Mastodon, bot_status: &mut BotStatus, config: &Config) -> Result<()> {
+    let now = chrono::Utc::now();
+    let last = match bot_status.last_roundup {
+        Some(last) => last,
+        None => {
+            bot_status.last_roundup = Some(now);
+            return Ok(());
+        },
+    };
+    // waits out quiet hours, but doesn't move when the next one is
+    if !config.roundup.is_due(last, now) || !config.schedule.allows(now) {
+        return Ok(());
+    }
+    let roundup = stats::roundup(&bot_status.known_templates, &bot_status.posts, last,
+                                 config.roundup.period(), &bot_status.off_leaderboard);
+    if bot_status.paused {
+        info!("skipping the roundup, we're paused");
+    } else if let Some((text, best)) = roundup {
+        info!("posting the roundup");
+        post_vec(mastodon, &split(&text, ""), None, None)?;
+        // one of them being deleted since shouldn't get the roundup posted
+        // again, so these can fail
+        for toot_id in best {
+            let _ = mastodon.unreblog(&toot_id);
+            if let Err(e) = mastodon.reblog(&toot_id) {
+                warn!("couldn't boost {} for the roundup: {}", toot_id, e);
+            }
+        }
+    }
+    bot_status.last_roundup = Some(now);
+    Ok(())
+}
+
+// Not backed off like the rest, a failure just waits for the next time around
+// so it doesn't hold up notifications, and one that keeps failing gets dropped
+// so it doesn't hold up everything behind it
+fn process_pending(mastodon: &Mastodon, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config) {
+    while let Some(action) = bot_status.pending.first().cloned() {
+        if let Err(e) = process_pending_action(mastodon, &action, bot_status, used_statuses, config) {
+            bot_status.pending_failures += 1;
+            if bot_status.pending_failures < PENDING_TRIES {
+                error!("{}, trying pending {:?} again next time", e, action);
+                return;
+            }
+            warn!("{}, giving up on pending {:?} after {} tries", e, action, PENDING_TRIES);
+        }
+        bot_status.pending.remove(0);
+        bot_status.pending_failures = 0;
+    }
+}
+
 fn process_follow(mastodon: &Mastodon, account: &account::Account) -> Result<()> {
     info!("followed by {}", &account.acct);
     mastodon.follow(&account.id).and(Ok(()))
@@ -256,22 +1005,32 @@ fn sleep(secs: u64) {
     std::thread::sleep(std::time::Duration::from_secs(secs));
 }
 
-fn get_status() -> BotStatus {
-    match File::open("status.bincode") {
+pub fn get_status() -> BotStatus {
+    match File::open(STATUS_FILE) {
         Ok(file) => {
-            bincode::deserialize_from(file).unwrap()
+            serde_json::from_reader(file).expect("couldn't parse status file")
         },
-        Err(_) => BotStatus {
-            // WARNING: Don't use this bot in the past
-            last_noti_date: chrono::DateTime::from_utc(
-                                chrono::naive::NaiveDateTime::from_timestamp(0, 0),
-                                chrono::Utc),
-            known_templates: vec![],
-            collection_toots: HashMap::new(),
+        Err(_) => {
+            // Starting fresh here would quietly throw away every template
+            if std::path::Path::new(LEGACY_STATUS_FILE).exists() {
+                panic!("found an old {}, run `cargo run --bin migrate-template-ids` first",
+                       LEGACY_STATUS_FILE);
+            }
+            BotStatus::new()
         }
     }
 }
 
+pub fn save_status(bot_status: &BotStatus) {
+    match File::create(STATUS_FILE) {
+        Ok(file) => {
+            serde_json::to_writer(file, bot_status)
+                .expect("couldn't serialize to file")
+        },
+        Err(_) => panic!("couldn't create/open status file")
+    };
+}
+
 fn sync_exp_backoff<F, T>(mut call: F) where
     F: FnMut() -> Result<T> {
     let mut time = 1;
@@ -293,7 +1052,7 @@ fn sync_exp_backoff<F, T>(mut call: F) where
     }
 }
 
-fn poll_notis(mastodon: &Mastodon, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>) -> Result<()> {
+fn poll_notis(mastodon: &Mastodon, bot_status: &mut BotStatus, used_statuses: &mut HashSet<String>, config: &Config) -> Result<()> {
     let mut last_noti_date_temp = bot_status.last_noti_date;
     let notis = mastodon.notifications()?;
     for noti in notis.initial_items {
@@ -309,8 +1068,10 @@ fn poll_notis(mastodon: &Mastodon, bot_status: &mut BotStatus, used_statuses: &m
         }
 
         match noti.notification_type {
-            notification::NotificationType::Mention => process_mention(&mastodon, noti, bot_status, used_statuses)?,
-            notification::NotificationType::Follow => process_follow(&mastodon, &noti.account)?,
+            notification::NotificationType::Mention => process_mention(&mastodon, noti, bot_status, used_statuses, config)?,
+            notification::NotificationType::Follow if !bot_status.blocked.contains(&noti.account.acct) => {
+                process_follow(&mastodon, &noti.account)?
+            },
             _ => (),
         }
     }
@@ -318,36 +1079,52 @@ fn poll_notis(mastodon: &Mastodon, bot_status: &mut BotStatus, used_statuses: &m
     Ok(())
 }
 
-fn poll_loop(mastodon: &Mastodon) {
+fn poll_loop(mastodon: &Mastodon, config: &Config) {
     let sleep_time = 60; // in seconds
 
     let mut bot_status = get_status();
     let mut used_statuses = HashSet::new();
+    for collection in bot_status.collection_toots.values_mut() {
+        let length = config.game_length(collection.mode());
+        collection.set_default_deadline(length);
+    }
 
-    let mut next_random = chrono::DateTime::from_utc(
-                                chrono::naive::NaiveDateTime::from_timestamp(0, 0),
-                                chrono::Utc);
-    let mut rng = rand::thread_rng();
-    let mut first_time = true;
+    let mut scheduler = Scheduler::new(config.policy, rand::thread_rng());
     loop {
         let now = chrono::Utc::now();
+        // None the very first time, and again after each post
+        let next_random = match bot_status.next_random {
+            Some(next_random) => next_random,
+            None => {
+                let next_random = config.schedule.next_allowed(now + scheduler.next_wait());
+                info!("next random post at {}", next_random);
+                bot_status.next_random = Some(next_random);
+                next_random
+            },
+        };
         if now >= next_random {
-            if !first_time {
-                sync_exp_backoff(|| post_random_madlib(&mastodon, &bot_status.known_templates, &mut used_statuses));
+            if bot_status.paused {
+                info!("skipping random post, we're paused");
+            } else {
+                sync_exp_backoff(|| scheduled_post(&mastodon, &mut scheduler, &mut bot_status, &mut used_statuses, config));
             }
-            let next_hours = rng.gen_range(1, 24);
-            next_random = now + chrono::Duration::hours(next_hours);
-            first_time = false;
+            bot_status.next_random = None;
         }
-        sync_exp_backoff(|| poll_notis(mastodon, &mut bot_status, &mut used_statuses));
-        // Serialize the bot status occasionally
-        match File::create("status.bincode") {
-            Ok(file) => {
-                bincode::serialize_into(file, &bot_status)
-                    .expect("couldn't serialize to file")
-            },
-            Err(_) => panic!("couldn't create/open status file")
+        sync_exp_backoff(|| poll_notis(mastodon, &mut bot_status, &mut used_statuses, config));
+        process_pending(mastodon, &mut bot_status, &mut used_statuses, config);
+        sync_exp_backoff(|| close_expired_collections(mastodon, &mut bot_status, &mut used_statuses, config));
+        sync_exp_backoff(|| update_collections(mastodon, &mut bot_status, config));
+        sync_exp_backoff(|| check_engagement(mastodon, &mut bot_status));
+        sync_exp_backoff(|| post_roundup(mastodon, &mut bot_status, config));
+        // the roundup wants everything since the last one, if there is one
+        let last_roundup = match config.roundup {
+            Roundup::Off => None,
+            _ => bot_status.last_roundup,
         };
+        stats::prune(&mut bot_status.posts, &mut bot_status.player_totals, last_roundup, chrono::Utc::now());
+        sync_exp_backoff(|| post_daily(mastodon, &mut bot_status, config));
+        // Serialize the bot status occasionally
+        save_status(&bot_status);
         sleep(sleep_time);
     }
 }
@@ -359,7 +1136,8 @@ pub fn run() {
         }
         Err(_) => register(),
     };
-    poll_loop(&mastodon)
+    let config = Config::load();
+    poll_loop(&mastodon, &config)
 }
 
 fn register() -> Mastodon {
@@ -377,7 +1155,9 @@ fn register() -> Mastodon {
 
 #[cfg(test)]
 mod test {
-    use super::{split, MAX_STATUS_LENGTH};
+    use super::{split, finished_posts, format_ccs, format_collection_toot, is_deleted, BotError, MAX_STATUS_LENGTH};
+    use config::{Config, Credit};
+    use template::Template;
     use std::iter::repeat;
     #[test]
     fn test_split() {
@@ -399,5 +1179,45 @@ mod test {
         assert_eq!(got.len(), 2);
         assert_eq!(got[1], append);
     }
+    #[test]
+    fn collection_toot_fits() {
+        let config = Config::default();
+        let short = Template::parse("t: a [noun: a fruit] [verbed]").unwrap();
+        let got = format_collection_toot(&short, Some("alice".to_string()), "hi!", &config);
+        assert!(got.contains("\n1. noun (a fruit)\n2. verbed\n"), "{}", got);
+        let long = Template::parse(&format!("t:{}", "a [noun: something long and round] ".repeat(15))).unwrap();
+        let got = format_collection_toot(&long, Some("alice".to_string()), "hi!", &config);
+        assert!(got.len() <= MAX_STATUS_LENGTH, "{}", got);
+        assert!(got.contains("\n15x noun\n"), "{}", got);
+        assert!(got.ends_with("cc @alice"));
+    }
+    #[test]
+    fn deleted_statuses() {
+        let api = |body| BotError::Api(::serde_json::from_str(body).unwrap());
+        assert!(is_deleted(&api(r#"{"error": "Record not found"}"#)));
+        // being rate limited doesn't mean it's gone
+        assert!(!is_deleted(&api(r#"{"error": "Too many requests"}"#)));
+    }
+    #[test]
+    fn cc_overflow() {
+        let accts: Vec<String> = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let accts: Vec<&String> = accts.iter().collect();
+        assert_eq!(format_ccs(&accts, 10), ("\ncc @a @b @c".to_string(), vec![]));
+        assert_eq!(format_ccs(&accts, 2), ("\ncc @a @b".to_string(), vec!["cc @c".to_string()]));
+        assert_eq!(format_ccs(&[], 2), (String::new(), vec![]));
+    }
+    #[test]
+    fn credits_placement() {
+        let credits = Some("**cat** — @alice".to_string());
+        assert_eq!(finished_posts("a cat", "\ncc @alice", None, Credit::Post),
+                   vec!["a cat\ncc @alice"]);
+        assert_eq!(finished_posts("a cat", "\ncc @alice", credits.clone(), Credit::Post),
+                   vec!["a cat\n\n**cat** — @alice\ncc @alice"]);
+        assert_eq!(finished_posts("a cat", "\ncc @alice", credits.clone(), Credit::Reply),
+                   vec!["a cat\ncc @alice", "who sent what:\n\n**cat** — @alice"]);
+        // no room, so it goes in a reply after all
+        let long_toot: String = repeat('.').take(MAX_STATUS_LENGTH - 10).collect();
+        assert_eq!(finished_posts(&long_toot, "", credits, Credit::Post).len(), 2);
+    }
 }
 
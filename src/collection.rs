@@ -1,51 +1,685 @@
 use std::collections::HashSet;
+use std::collections::HashMap;
+use std::cmp::Reverse;
+use rand::Rng;
+use regex::Regex;
+use chrono::{DateTime, Duration, Utc};
 use AccountID;
 use sanitize_all;
 use pos::*;
-use template::Template;
+use filter::Filter;
+use template::{Template, Strictness, Mode};
+use library::{Library, TemplateID};
+use bot::StatusID;
 
-pub type Response = (POS, String);
+// Which blank a response is for, as written, before we look at the template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    // `noun: volcano`, whichever noun is free
+    Pos(POS),
+    // `3: volcano`, counting every blank from 1
+    Blank(usize),
+    // `noun#2: volcano`, counting just the nouns from 1
+    NthPos(POS, usize),
+}
+
+pub type Answer = (Target, String);
+
+// An Answer once we've checked it against the template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub pos: POS,
+    pub word: String,
+    // from 0, None if any blank for pos will do
+    pub blank: Option<usize>,
+}
+impl Response {
+    pub fn new(pos: POS, word: &str) -> Self {
+        Response {
+            pos,
+            word: word.to_string(),
+            blank: None,
+        }
+    }
+}
+
+// A Response once we know who sent it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    pub pos: POS,
+    pub word: String,
+    // empty for the ones migrate-template-ids brought over, we never knew
+    #[serde(default)]
+    pub author: AccountID,
+    #[serde(default)]
+    pub blank: Option<usize>,
+    // the reply it came in, so we notice if that gets edited or deleted
+    #[serde(default)]
+    pub status: Option<StatusID>,
+    // favourites on that reply, last we checked
+    #[serde(default)]
+    pub votes: u64,
+}
+
+// How to pick when more words come in for a POS than the template has blanks
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    // every word sent in has the same chance
+    Random,
+    // use as many different people's words as we can, randomly otherwise
+    Fair,
+    // the most favourited replies win, so the game stays open until its
+    // deadline instead of finishing once every blank has a word
+    Votes,
+}
+impl Default for Selection {
+    fn default() -> Self {
+        Selection::Fair
+    }
+}
+
+// How much of a game one person gets to fill in
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Limits {
+    // words one person can have in the game at once, None for as many as they like
+    pub max_words: Option<usize>,
+    // the same as max_words = 1, for games where that's the whole point
+    pub one_word_each: bool,
+    // how long someone has to wait after their words are taken before they
+    // can send more, 0 for not at all
+    pub cooldown_minutes: i64,
+}
+impl Limits {
+    fn max_words(&self) -> Option<usize> {
+        if self.one_word_each {
+            Some(1)
+        } else {
+            self.max_words
+        }
+    }
+    // for the collection toot, None if there aren't any
+    pub fn describe(&self) -> Option<String> {
+        let mut rules = Vec::new();
+        match self.max_words() {
+            Some(1) => rules.push("one word each".to_string()),
+            Some(max) => rules.push(format!("up to {} words each", max)),
+            None => (),
+        }
+        if self.cooldown_minutes > 0 {
+            rules.push(format!("wait {} minutes before sending more", self.cooldown_minutes));
+        }
+        if rules.is_empty() {
+            None
+        } else {
+            Some(rules.join(", "))
+        }
+    }
+    // Adds one rule, written the way describe writes it ("up to 3 words
+    // each", "wait 30 minutes"), returns false if it isn't one
+    pub fn add_rule(&mut self, rule: &str) -> bool {
+        let rule = rule.trim().to_lowercase();
+        if rule == "one word each" {
+            self.one_word_each = true;
+            return true;
+        }
+        let max_words = Regex::new(r"^(?:up to )?(\d+) words? each$").unwrap();
+        let cooldown = Regex::new(r"^wait (\d+) minutes?(?: before sending more)?$").unwrap();
+        if let Some(max) = max_words.captures(&rule).and_then(|caps| caps[1].parse().ok()) {
+            self.max_words = Some(max);
+        } else if let Some(minutes) = cooldown.captures(&rule).and_then(|caps| caps[1].parse().ok()) {
+            self.cooldown_minutes = minutes;
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+// takes one of pos out of what's still open, if there's one left
+fn take(open: &mut HashMap<POS, usize>, pos: POS) -> bool {
+    match open.get_mut(&pos) {
+        Some(count) if *count > 0 => {
+            *count -= 1;
+            true
+        },
+        _ => false,
+    }
+}
+
+fn fits(open: &HashMap<POS, usize>, pos: POS) -> bool {
+    open.get(&pos).map_or(false, |count| *count > 0)
+}
+
+fn format_responses(resps: &[Response]) -> String {
+    resps.iter()
+        .map(|resp| match resp.blank {
+            Some(blank) => format!("{}: {} ({})", blank + 1, resp.word, pos_to_str(&resp.pos)),
+            None => format!("{}: {}", pos_to_str(&resp.pos), resp.word),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// "**volcano** — @alice" for each blank someone we know of filled, in order,
+// leaving the @ off for anyone `mention` says doesn't want one
+// Goes by the words in the template, so do it before bold_credited
+pub fn format_credits<F>(template: &Template, credits: &[Option<AccountID>], mention: F) -> Option<String>
+        where F: Fn(&AccountID) -> bool {
+    let lines: Vec<String> = template.blanks().iter()
+        .zip(credits)
+        .filter_map(|(blank, author)| match (&blank.text, author) {
+            (Some(word), Some(author)) if mention(author) => Some(format!("**{}** — @{}", word, author)),
+            (Some(word), Some(author)) => Some(format!("**{}** — {}", word, author)),
+            _ => None,
+        })
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+// Makes the words people sent stand out from the rest of the template
+pub fn bold_credited(template: &mut Template, credits: &[Option<AccountID>]) {
+    let blanks = template.body.iter_mut().filter(|token| token.pos.is_some());
+    for (blank, author) in blanks.zip(credits) {
+        if let (Some(word), Some(_)) = (&mut blank.text, author) {
+            *word = format!("**{}**", word);
+        }
+    }
+}
+
+// Works out which blank each answer is for, and makes sure it's there
+pub fn place(template: &Template, answers: Vec<Answer>) -> Result<Vec<Response>, ParseError> {
+    let blanks: Vec<POS> = template.blanks().iter()
+        .filter_map(|token| token.pos)
+        .collect();
+    let mut resps = Vec::new();
+    for (target, word) in answers {
+        let resp = match target {
+            Target::Pos(pos) => Response { pos, word, blank: None },
+            Target::Blank(n) => {
+                let blank = n.checked_sub(1)
+                    .filter(|blank| *blank < blanks.len())
+                    .ok_or(ParseError::NoSuchBlank(n))?;
+                Response { pos: blanks[blank], word, blank: Some(blank) }
+            },
+            Target::NthPos(pos, n) => {
+                let blank = n.checked_sub(1)
+                    .and_then(|nth| blanks.iter()
+                        .enumerate()
+                        .filter(|(_, blank_pos)| **blank_pos == pos)
+                        .map(|(blank, _)| blank)
+                        .nth(nth))
+                    .ok_or(ParseError::NoSuchNth(pos, n))?;
+                Response { pos, word, blank: Some(blank) }
+            },
+        };
+        resps.push(resp);
+    }
+    Ok(resps)
+}
+
+// Runs each response past `tag` (the tagger, outside of tests) and, depending
+// on how strict the template is, drops the ones it disagrees with
+// Returns what's left and, if anything was off, what to tell them
+pub fn tagger_check<F>(resps: Vec<Response>, strictness: Strictness, mut tag: F) -> (Vec<Response>, Option<String>)
+        where F: FnMut(&str) -> Option<POS> {
+    if strictness == Strictness::Off {
+        return (resps, None);
+    }
+    let mut kept = Vec::new();
+    let mut complaints = Vec::new();
+    for resp in resps {
+        let tagged = tag(&resp.word);
+        if tagged == Some(resp.pos) {
+            kept.push(resp);
+            continue;
+        }
+        let guess = match tagged {
+            Some(tagged) => format!(" (more like a {})", pos_to_str(&tagged)),
+            None => String::new(),
+        };
+        complaints.push(format!("\"{}\" doesn't look like a {} to me{}",
+                                resp.word, pos_to_str(&resp.pos), guess));
+        if strictness == Strictness::Warn {
+            kept.push(resp);
+        }
+    }
+    if complaints.is_empty() {
+        return (kept, None);
+    }
+    let verdict = match strictness {
+        Strictness::Reject => "so i left those out, this is a serious game",
+        _ => "using them anyway!",
+    };
+    (kept, Some(format!("{}\n{}", complaints.join("\n"), verdict)))
+}
+
+// How one toot's responses measure up against what a game needs
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct ResponseCheck {
+    // there was a blank nobody had sent anything for yet
+    pub accepted: Vec<Response>,
+    // every blank for it (or the one they asked for) already has a word, so
+    // it'll compete with those
+    pub surplus: Vec<Response>,
+    // the template doesn't have that POS at all, these get dropped
+    pub unneeded: Vec<Response>,
+    // everything the template asks for, for telling people what it does need
+    pub needs: HashMap<POS, usize>,
+}
+impl ResponseCheck {
+    // the parts that get kept
+    pub fn kept(&self) -> Vec<Response> {
+        self.accepted.iter().chain(self.surplus.iter()).cloned().collect()
+    }
+    // None if everything was simply accepted, otherwise what to tell them
+    pub fn explain(&self) -> Option<String> {
+        if self.surplus.is_empty() && self.unneeded.is_empty() {
+            return None;
+        }
+        let mut parts = Vec::new();
+        if !self.accepted.is_empty() {
+            parts.push(format!("got: {}", format_responses(&self.accepted)));
+        }
+        if !self.surplus.is_empty() {
+            parts.push(format!("extra, there's already a word for every one of \
+                                these so they'll be picked from at the end: {}",
+                               format_responses(&self.surplus)));
+        }
+        if !self.unneeded.is_empty() {
+            parts.push(format!("not used, this template doesn't have any: {}",
+                               format_responses(&self.unneeded)));
+            parts.push(format!("what it has is: {}", format_requirements(&self.needs)));
+        }
+        Some(parts.join("\n"))
+    }
+}
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct CollectionStatus {
     // template is not partially resolved, because it's re-used...
-    template_id: usize,
+    template_id: TemplateID,
     participants: HashSet<AccountID>,
-    // ...instead suggestions are stored like this(?)
-    resolved: Vec<Response>,
+    // whoever sent the template that started it, None if the bot did
+    #[serde(default)]
+    starter: Option<AccountID>,
+    // ...instead suggestions are stored like this, all of them, even the ones
+    // there's no room for, so we can choose when it's done
+    resolved: Vec<Contribution>,
+    #[serde(default)]
+    selection: Selection,
+    // games from before we kept track count as opened when we first load them
+    #[serde(default = "Utc::now")]
+    opened: DateTime<Utc>,
+    #[serde(default)]
+    deadline: Option<DateTime<Utc>>,
+    // the last "still need" reply, None if there hasn't been one
+    #[serde(default)]
+    last_update: Option<DateTime<Utc>>,
+    #[serde(default)]
+    limits: Limits,
+    // when each person last had words taken, for the cooldown
+    #[serde(default)]
+    last_sent: HashMap<AccountID, DateTime<Utc>>,
+    // whether people want to be mentioned about just this game, which wins
+    // over what they want everywhere else
+    #[serde(default)]
+    mention_prefs: HashMap<AccountID, bool>,
+    // manual or hybrid, games are never automatic
+    #[serde(default = "manual")]
+    mode: Mode,
+    // replies we couldn't take any words from and who sent them, so an edit
+    // that fixes one still gets looked at
+    #[serde(default)]
+    rejected: Vec<(StatusID, AccountID)>,
+}
+
+fn manual() -> Mode {
+    Mode::Manual
 }
 impl CollectionStatus {
-    pub fn new(template_id: usize, acct: AccountID) -> Self {
-        let mut participants = HashSet::new();
-        participants.insert(acct);
+    pub fn new(template_id: TemplateID, acct: AccountID) -> Self {
+        let mut collection = Self::for_template(template_id);
+        collection.participants.insert(acct.clone());
+        collection.starter = Some(acct);
+        collection
+    }
+    // a game nobody asked for, which the bot opened on its own
+    pub fn for_template(template_id: TemplateID) -> Self {
         Self {
             template_id,
-            participants,
+            participants: HashSet::new(),
+            starter: None,
             resolved: Vec::new(),
+            selection: Selection::default(),
+            opened: Utc::now(),
+            deadline: None,
+            last_update: None,
+            limits: Limits::default(),
+            last_sent: HashMap::new(),
+            mention_prefs: HashMap::new(),
+            mode: Mode::Manual,
+            rejected: Vec::new(),
+        }
+    }
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection;
+    }
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+    // how much longer someone has to wait to send more, None if they don't
+    pub fn wait_left(&self, author: &AccountID, now: DateTime<Utc>) -> Option<Duration> {
+        let last = self.last_sent.get(author)?;
+        let wait = *last + Duration::minutes(self.limits.cooldown_minutes) - now;
+        if wait > Duration::zero() {
+            Some(wait)
+        } else {
+            None
+        }
+    }
+    pub fn mark_sent(&mut self, author: &AccountID, now: DateTime<Utc>) {
+        self.last_sent.insert(author.clone(), now);
+    }
+    // Cuts what check_responses kept down to as many words as someone has
+    // left, the ones for empty blanks first, and says so if that wasn't all
+    // of them. Words the game can't use never count against the limit
+    pub fn limit_responses(&self, author: &AccountID, check: &mut ResponseCheck) -> Option<String> {
+        let max = self.limits.max_words()?;
+        let have = self.resolved.iter()
+            .filter(|contribution| &contribution.author == author)
+            .count();
+        let left = max.saturating_sub(have);
+        let mut dropped = Vec::new();
+        if check.accepted.len() > left {
+            dropped = check.accepted.split_off(left);
+        }
+        let left = left - check.accepted.len();
+        if check.surplus.len() > left {
+            dropped.append(&mut check.surplus.split_off(left));
+        }
+        if dropped.is_empty() {
+            return None;
+        }
+        Some(format!("you can only have {} word(s) in this game, so i left out: {}\n\
+                      (!retract takes back what you sent, if you'd rather swap)",
+                     max, format_responses(&dropped)))
+    }
+    pub fn set_deadline(&mut self, deadline: DateTime<Utc>) {
+        self.deadline = Some(deadline);
+    }
+    // for games that were opened before they had deadlines
+    pub fn set_default_deadline(&mut self, length: Duration) {
+        if self.deadline.is_none() {
+            self.deadline = Some(self.opened + length);
+        }
+    }
+    pub fn opened(&self) -> DateTime<Utc> {
+        self.opened
+    }
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        self.deadline
+    }
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.deadline.map_or(false, |deadline| now >= deadline)
+    }
+    // true if it's been `interval` since it was opened or last updated
+    pub fn needs_update(&self, now: DateTime<Utc>, interval: Duration) -> bool {
+        now >= self.last_update.unwrap_or(self.opened) + interval
+    }
+    pub fn mark_updated(&mut self, now: DateTime<Utc>) {
+        self.last_update = Some(now);
+    }
+    // Sorts responses by whether the template has room for them, counting
+    // every contribution so far (not just the ones that'd get picked)
+    // None if the template is gone
+    pub fn check_responses(&self, templates: &Library, resps: Vec<Response>) -> Option<ResponseCheck> {
+        let needs = templates.get(self.template_id)?.requirements();
+        let mut open = needs.clone();
+        let mut taken = HashSet::new();
+        for contribution in &self.resolved {
+            if contribution.blank.map_or(true, |blank| taken.insert(blank)) {
+                take(&mut open, contribution.pos);
+            }
+        }
+        let mut check = ResponseCheck::default();
+        for resp in resps {
+            let room = match resp.blank {
+                Some(blank) => taken.insert(blank) && take(&mut open, resp.pos),
+                None => take(&mut open, resp.pos),
+            };
+            if !needs.contains_key(&resp.pos) {
+                check.unneeded.push(resp);
+            } else if room {
+                check.accepted.push(resp);
+            } else {
+                check.surplus.push(resp);
+            }
+        }
+        check.needs = needs;
+        Some(check)
+    }
+    // "still need: 1x noun, 2x verbs", None if the template is gone
+    pub fn progress(&self, templates: &Library) -> Option<String> {
+        let remaining = self.remaining(templates)?;
+        // a full game that's still open is waiting on its deadline (or votes)
+        if remaining.is_empty() {
+            return Some(match self.selection {
+                Selection::Votes => "every blank has a word, favourite the ones you like best \
+                                     before it closes".to_string(),
+                _ => "every blank has a word, it gets finished when it closes".to_string(),
+            });
+        }
+        Some(format!("still need: {}", format_requirements(&remaining)))
+    }
+    pub fn add_responses(&mut self, author: &AccountID, status: Option<&StatusID>, resps: Vec<Response>) {
+        for resp in resps {
+            self.resolved.push(Contribution {
+                pos: resp.pos,
+                word: resp.word,
+                author: author.clone(),
+                blank: resp.blank,
+                status: status.cloned(),
+                votes: 0,
+            });
+        }
+    }
+    // every reply we took words from (or couldn't), and who sent it
+    pub fn response_statuses(&self) -> Vec<(StatusID, AccountID)> {
+        let mut statuses: Vec<(StatusID, AccountID)> = Vec::new();
+        for contribution in &self.resolved {
+            if let Some(status) = &contribution.status {
+                if !statuses.iter().any(|(seen, _)| seen == status) {
+                    statuses.push((status.clone(), contribution.author.clone()));
+                }
+            }
         }
+        statuses.extend(self.rejected.iter().cloned());
+        statuses
     }
-    pub fn add_responses(&mut self, mut resps: Vec<Response>) {
-        self.resolved.append(&mut resps);
+    // a reply with words we couldn't use, see refresh_responses
+    pub fn reject_status(&mut self, status: &StatusID, author: &AccountID) {
+        if !self.rejected.iter().any(|(seen, _)| seen == status) {
+            self.rejected.push((status.clone(), author.clone()));
+        }
+    }
+    pub fn set_votes(&mut self, status: &StatusID, votes: u64) {
+        for contribution in &mut self.resolved {
+            if contribution.status.as_ref() == Some(status) {
+                contribution.votes = votes;
+            }
+        }
+    }
+    // drops the words from one reply, returns how many there were
+    pub fn remove_status(&mut self, status: &StatusID) -> usize {
+        self.rejected.retain(|(seen, _)| seen != status);
+        let before = self.resolved.len();
+        self.resolved.retain(|contribution| contribution.status.as_ref() != Some(status));
+        before - self.resolved.len()
+    }
+    // drops every word someone sent, returns how many there were
+    pub fn retract(&mut self, author: &AccountID) -> usize {
+        let before = self.resolved.len();
+        self.resolved.retain(|contribution| &contribution.author != author);
+        before - self.resolved.len()
+    }
+    pub fn starter(&self) -> Option<&AccountID> {
+        self.starter.as_ref()
     }
     pub fn add_participant(&mut self, participant: AccountID) {
         self.participants.insert(participant);
     }
-    pub fn get_participant_ats(&self) -> String {
-        let mut text = String::from("\ncc");
-        for participant in &self.participants {
-            text.push_str(" @");
-            text.push_str(participant);
+    pub fn set_mention_pref(&mut self, acct: &AccountID, wants: bool) {
+        self.mention_prefs.insert(acct.clone(), wants);
+    }
+    // Whether someone wants to be mentioned about this game, going by what
+    // they said for this game, then what they said in general (`prefs`)
+    pub fn wants_mention(&self, acct: &AccountID, prefs: &HashMap<AccountID, bool>) -> bool {
+        *self.mention_prefs.get(acct)
+            .or_else(|| prefs.get(acct))
+            .unwrap_or(&true)
+    }
+    // everyone who played and wants to hear about it, sorted so it's the same
+    // every time
+    pub fn to_mention(&self, prefs: &HashMap<AccountID, bool>) -> Vec<&AccountID> {
+        let mut participants: Vec<&AccountID> = self.participants.iter()
+            .filter(|participant| self.wants_mention(participant, prefs))
+            .collect();
+        participants.sort();
+        participants
+    }
+    pub fn strictness(&self, templates: &Library) -> Strictness {
+        templates.get(self.template_id)
+            .map_or(Strictness::Off, |template| template.strictness)
+    }
+    pub fn template_id(&self) -> TemplateID {
+        self.template_id
+    }
+    pub fn participant_count(&self) -> usize {
+        self.participants.len()
+    }
+    // what's still needed once everything we have so far is filled in, None
+    // if the template is gone
+    pub fn remaining(&self, templates: &Library) -> Option<HashMap<POS, usize>> {
+        Some(self.filled(templates)?.requirements())
+    }
+    // Which of the contributions that'll go in any blank make it in, at most
+    // `needed` of each POS
+    fn choose<R: Rng>(&self, needed: &HashMap<POS, usize>, rng: &mut R) -> Vec<&Contribution> {
+        let mut open = needed.clone();
+        let mut left: Vec<&Contribution> = self.resolved.iter()
+            .filter(|contribution| contribution.blank.is_none())
+            .collect();
+        rng.shuffle(&mut left);
+        let mut chosen = Vec::new();
+        match self.selection {
+            // votes never get here, see filled_with
+            Selection::Random | Selection::Votes => {
+                for contribution in left {
+                    if take(&mut open, contribution.pos) {
+                        chosen.push(contribution);
+                    }
+                }
+            },
+            Selection::Fair => loop {
+                // Every round, everyone gets at most one more word in. People
+                // with the fewest words that still fit go first, so someone
+                // who only sent a noun isn't crowded out by someone who could
+                // just as well have filled a verb
+                let mut authors: Vec<&AccountID> = Vec::new();
+                let mut options: HashMap<&AccountID, usize> = HashMap::new();
+                for contribution in &left {
+                    if fits(&open, contribution.pos) {
+                        let count = options.entry(&contribution.author).or_insert(0);
+                        if *count == 0 {
+                            authors.push(&contribution.author);
+                        }
+                        *count += 1;
+                    }
+                }
+                if authors.is_empty() {
+                    break;
+                }
+                // stable, so ties stay in shuffled order
+                authors.sort_by_key(|author| options[author]);
+                for author in authors {
+                    let pick = left.iter().position(|contribution| {
+                        &contribution.author == author && fits(&open, contribution.pos)
+                    });
+                    if let Some(i) = pick {
+                        let contribution = left.remove(i);
+                        take(&mut open, contribution.pos);
+                        chosen.push(contribution);
+                    }
+                }
+            },
         }
-        text
+        chosen
+    }
+    // the template with the responses we picked put in, None if it's gone
+    pub fn filled(&self, templates: &Library) -> Option<Template> {
+        Some(self.credited(templates)?.0)
     }
-    // returns None if not enough data and Some(resolved) if there is
-    pub fn check_done(&self, templates: &Vec<Template>) -> Option<String> {
-        let mut template_clone = templates[self.template_id].clone();
-        // TODO: select randomly
-        for resp in &self.resolved {
-            template_clone.insert_placeholder(resp.0, resp.1.clone());
+    // filled, and who sent the word in each blank (None where nobody did,
+    // or we don't know who)
+    pub fn credited(&self, templates: &Library) -> Option<(Template, Vec<Option<AccountID>>)> {
+        self.filled_with(templates, &mut rand::thread_rng())
+    }
+    fn filled_with<R: Rng>(&self, templates: &Library, rng: &mut R) -> Option<(Template, Vec<Option<AccountID>>)> {
+        let mut template_clone = templates.get(self.template_id)?.clone();
+        let mut credits = vec![None; template_clone.blanks().len()];
+        let mut put = |template: &mut Template, contribution: &Contribution| {
+            let blank = contribution.blank.or_else(|| template.free_blank(contribution.pos));
+            if let Some(blank) = blank {
+                if template.fill_blank(blank, contribution.word.clone()) && contribution.author != "" {
+                    credits[blank] = Some(contribution.author.clone());
+                }
+            }
+        };
+        if self.selection == Selection::Votes {
+            // every word competes on votes alone, whichever blank it's for
+            let mut candidates: Vec<&Contribution> = self.resolved.iter().collect();
+            rng.shuffle(&mut candidates);
+            // stable, so ties stay in shuffled order
+            candidates.sort_by_key(|contribution| Reverse(contribution.votes));
+            for contribution in candidates {
+                put(&mut template_clone, contribution);
+            }
+        } else {
+            // people who asked for a particular blank get it, one of them at
+            // random if there's a few, and the rest is shared out from what's left
+            let mut targeted: Vec<&Contribution> = self.resolved.iter()
+                .filter(|contribution| contribution.blank.is_some())
+                .collect();
+            rng.shuffle(&mut targeted);
+            for contribution in targeted {
+                put(&mut template_clone, contribution);
+            }
+            let needed = template_clone.requirements();
+            for contribution in self.choose(&needed, rng) {
+                put(&mut template_clone, contribution);
+            }
         }
-        template_clone.check_done()
+        Some((template_clone, credits))
+    }
+    // returns None if not enough data (or the template is gone) and
+    // Some(resolved) if there is
+    pub fn check_done(&self, templates: &Library) -> Option<String> {
+        self.filled(templates)?.check_done()
     }
 }
 
@@ -54,6 +688,10 @@ pub enum ParseError {
     DeclaredTwice,
     UnknownPOS(String),
     ExpectedWord,
+    Blocked,
+    NoSuchBlank(usize),
+    NoSuchNth(POS, usize),
+    UnclosedQuote,
 }
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -62,6 +700,11 @@ impl std::fmt::Display for ParseError {
             ExpectedWord => write!(f, "gave a pos: but then no word before newline/comma"),
             DeclaredTwice => write!(f, "didn't expect two declarations like `noun: verb: thing`"),
             UnknownPOS(given) => write!(f, "unknown part of speech {}", given),
+            // don't repeat the word back, that's the whole point
+            Blocked => write!(f, "one of those words isn't allowed here"),
+            NoSuchBlank(n) => write!(f, "there's no blank {} in this one", n),
+            NoSuchNth(pos, n) => write!(f, "there's no {}#{} in this one", pos_to_str(pos), n),
+            UnclosedQuote => write!(f, "a \"quote\" was never closed"),
         }
     }
 }
@@ -71,95 +714,459 @@ impl std::error::Error for ParseError {
     }
 }
 
+// `noun`, `3` or `noun#2`, the bit before the colon
+fn parse_target(declared: &str) -> Result<Target, ParseError> {
+    const NTH: char = '#';
+    let unknown = || ParseError::UnknownPOS(declared.to_string());
+    if let Ok(n) = declared.parse() {
+        return Ok(Target::Blank(n));
+    }
+    let mut parts = declared.splitn(2, NTH);
+    let pos = str_to_pos(parts.next().unwrap().trim()).ok_or_else(unknown)?;
+    match parts.next() {
+        Some(n) => Ok(Target::NthPos(pos, n.trim().parse().map_err(|_| unknown())?)),
+        None => Ok(Target::Pos(pos)),
+    }
+}
+
 /// actually parses one toot that may contain many responses
-pub fn parse_response(resp: &str) -> Result<Vec<Response>, ParseError> {
+/// if any of them has a word the filter doesn't allow, none of them count
+/// which blanks they're for gets checked later, by `place`
+pub fn parse_response(resp: &str, filter: &Filter) -> Result<Vec<Answer>, ParseError> {
     let resp = sanitize_all(resp);
 
     const DECLARE: char = ':';
     const SEP: char = '\n';
     const SEP2: char = ',';
+    // phones like to turn "these" into “these”
+    const QUOTES: [(char, char); 3] = [('"', '"'), ('“', '”'), ('”', '”')];
     let mut responses = Vec::new();
     let mut chunk = String::new();
-    let mut pos = None;
+    let mut target = None;
+    // the quote that'd end the one we're in
+    let mut closing = None;
+    // once there's a quoted word, it's the whole word
+    let mut quoted = None;
+    // the part after the colon, as long as it's not just spaces
+    let mut finish = |target, chunk: &str, quoted: Option<String>| {
+        let word = quoted.unwrap_or_else(|| chunk.trim().to_string());
+        if word == "" {
+            return Err(ParseError::ExpectedWord);
+        }
+        responses.push((target, word));
+        Ok(())
+    };
     for c in resp.chars() {
+        if let Some(close) = closing {
+            if c == close {
+                closing = None;
+                quoted = Some(chunk.trim().to_string());
+                chunk = String::new();
+            } else {
+                chunk.push(c);
+            }
+            continue;
+        }
         match c {
             DECLARE => {
-                if pos.is_some() {
+                if target.is_some() {
                     return Err(ParseError::DeclaredTwice);
                 }
-                pos = match str_to_pos(&chunk) {
-                    Some(p) => Some(p),
-                    None => return Err(ParseError::UnknownPOS(chunk)),
-                };
+                target = Some(parse_target(chunk.trim())?);
                 chunk = String::new();
             },
             SEP | SEP2 => {
-                if let Some(pos) = pos {
-                    if chunk != "" {
-                        responses.push((pos, chunk));
-                    } else {
-                        return Err(ParseError::ExpectedWord);
-                    }
+                if let Some(target) = target {
+                    finish(target, &chunk, quoted)?;
                 }
                 // if there wasn't anything, then this is noise / comment
-                pos = None;
+                target = None;
+                quoted = None;
+                chunk = String::new();
+            },
+            // quotes only mean something right after the colon
+            _ if target.is_some() && chunk.trim() == "" && quoted.is_none()
+                    && QUOTES.iter().any(|(open, _)| *open == c) => {
+                closing = QUOTES.iter().find(|(open, _)| *open == c).map(|(_, close)| *close);
                 chunk = String::new();
             },
-            // ignore these whitespaces
-            ' ' | '\t' => (),
             _ => {
                 chunk.push(c);
             }
         };
     }
-    // deal with the last bit (DRY?)
-    if let Some(pos) = pos {
-        if chunk != "" {
-            responses.push((pos, chunk));
-        } else {
-            return Err(ParseError::ExpectedWord);
-        }
+    if closing.is_some() {
+        return Err(ParseError::UnclosedQuote);
+    }
+    // deal with the last bit
+    if let Some(target) = target {
+        finish(target, &chunk, quoted)?;
+    }
+    if responses.iter().any(|(_, word)| !filter.allows(word)) {
+        return Err(ParseError::Blocked);
     }
     Ok(responses)
 }
 
 #[cfg(test)]
 mod test {
-    use super::{parse_response, POS, CollectionStatus};
+    use super::{parse_response, place, POS, Target, Response, CollectionStatus, ParseError};
+    use filter::Filter;
     #[test]
     fn one_decl() {
-        let got = parse_response("nouns: cars");
-        let exp = vec![(POS::Nouns, "cars".to_string())];
+        let got = parse_response("nouns: cars", &Filter::default());
+        let exp = vec![(Target::Pos(POS::Nouns), "cars".to_string())];
         assert_eq!(got, Ok(exp));
     }
     #[test]
     fn two_decl() {
-        let got = parse_response("verbs: eats, uh: grr");
+        let got = parse_response("verbs: eats, uh: grr", &Filter::default());
         let exp = vec![
-            (POS::Verbs, "eats".to_string()),
-            (POS::Uh, "grr".to_string()),
+            (Target::Pos(POS::Verbs), "eats".to_string()),
+            (Target::Pos(POS::Uh), "grr".to_string()),
         ];
         assert_eq!(got, Ok(exp));
     }
     #[test]
     fn comment_lines() {
-        let got = parse_response("<a href=aoesutnhaoesn>@madlibs</a> verbs: eats, and what else, uhhhh, okay so, uh: grr");
+        let got = parse_response("<a href=aoesutnhaoesn>@madlibs</a> verbs: eats, and what else, uhhhh, okay so, uh: grr", &Filter::default());
         let exp = vec![
-            (POS::Verbs, "eats".to_string()),
-            (POS::Uh, "grr".to_string()),
+            (Target::Pos(POS::Verbs), "eats".to_string()),
+            (Target::Pos(POS::Uh), "grr".to_string()),
         ];
         assert_eq!(got, Ok(exp));
     }
     #[test]
+    fn multi_word() {
+        let got = parse_response("noun:  ice cream \n verbed: fell\tover, uh: grr", &Filter::default());
+        let exp = vec![
+            (Target::Pos(POS::Noun), "ice cream".to_string()),
+            (Target::Pos(POS::Verbed), "fell\tover".to_string()),
+            (Target::Pos(POS::Uh), "grr".to_string()),
+        ];
+        assert_eq!(got, Ok(exp));
+        assert_eq!(parse_response("noun #2: cat", &Filter::default()),
+                   Ok(vec![(Target::NthPos(POS::Noun, 2), "cat".to_string())]));
+        assert_eq!(parse_response("noun:   , verb: x", &Filter::default()), Err(ParseError::ExpectedWord));
+    }
+    #[test]
+    fn quoted() {
+        let got = parse_response("noun: \"salt, pepper\", proper: &quot;Dr: No&quot;\n2: “a 6\" sub”", &Filter::default());
+        let exp = vec![
+            (Target::Pos(POS::Noun), "salt, pepper".to_string()),
+            (Target::Pos(POS::Proper), "Dr: No".to_string()),
+            (Target::Blank(2), "a 6\" sub".to_string()),
+        ];
+        assert_eq!(got, Ok(exp));
+        assert_eq!(parse_response("noun: a 6\" sub", &Filter::default()),
+                   Ok(vec![(Target::Pos(POS::Noun), "a 6\" sub".to_string())]));
+        assert_eq!(parse_response("noun: \"salt, pepper", &Filter::default()), Err(ParseError::UnclosedQuote));
+        assert_eq!(parse_response("noun: \"\"", &Filter::default()), Err(ParseError::ExpectedWord));
+    }
+    #[test]
+    fn blocked_words() {
+        let filter = Filter::new(vec!["grr"]);
+        assert_eq!(parse_response("verbs: eats, uh: grr", &filter), Err(ParseError::Blocked));
+        assert!(parse_response("verbs: eats, uh: grrr", &filter).is_ok());
+    }
+    #[test]
     fn resolve() {
         use template::Template;
+        use library::Library;
         let req = Template::parse("titled: i need a [noun] another [noun] and a [verb]").unwrap();
-        let templates = vec![req];
-        let resps = parse_response("noun: thing, noun: table, verb: bore").unwrap();
-        let mut cs = CollectionStatus::new(0, "cosine@anticapitalist.party".to_string());
-        cs.add_responses(resps);
-        let exp = "titled:\n i need a thing another table and a bore".to_string();
-        assert_eq!(cs.check_done(&templates), Some(exp));
+        let mut templates = Library::new();
+        let id = templates.add(req);
+        let resps = parse_response("noun: thing, noun: table, verb: bore", &Filter::default()).unwrap();
+        let resps = place(templates.get(id).unwrap(), resps).unwrap();
+        let cosine = "cosine@anticapitalist.party".to_string();
+        let mut cs = CollectionStatus::new(id, cosine.clone());
+        cs.add_responses(&cosine, None, resps);
+        // the two nouns can land in either blank
+        let got = cs.check_done(&templates).unwrap();
+        assert!(got == "titled:\n i need a thing another table and a bore"
+                || got == "titled:\n i need a table another thing and a bore", "{}", got);
+    }
+    #[test]
+    fn positional() {
+        use template::Template;
+        use library::Library;
+        let mut templates = Library::new();
+        let id = templates.add(Template::parse("t: a [noun] and a [noun] [verbed]").unwrap());
+        let template = templates.get(id).unwrap();
+        let answers = parse_response("2: pear, noun#1: apple, verbed: ate", &Filter::default()).unwrap();
+        assert_eq!(answers[1].0, Target::NthPos(POS::Noun, 1));
+        let resps = place(template, answers).unwrap();
+        assert_eq!(resps[0].blank, Some(1));
+        assert_eq!(resps[1].blank, Some(0));
+        let placed = |text| place(template, parse_response(text, &Filter::default()).unwrap());
+        assert_eq!(placed("4: plum"), Err(ParseError::NoSuchBlank(4)));
+        assert_eq!(placed("0: plum"), Err(ParseError::NoSuchBlank(0)));
+        assert_eq!(placed("noun#3: plum"), Err(ParseError::NoSuchNth(POS::Noun, 3)));
+        assert_eq!(parse_response("noun#two: plum", &Filter::default()),
+                   Err(ParseError::UnknownPOS("noun#two".to_string())));
+        let alice = "alice".to_string();
+        let mut cs = CollectionStatus::new(id, alice.clone());
+        cs.add_responses(&alice, None, resps);
+        assert_eq!(cs.check_done(&templates).unwrap(), "t:\n a apple and a pear ate");
+        let mut cs = CollectionStatus::new(id, alice.clone());
+        cs.add_responses(&alice, None, placed("2: pear, verbed: ate").unwrap());
+        let check = cs.check_responses(&templates, placed("2: plum, noun: kiwi").unwrap()).unwrap();
+        assert_eq!(check.surplus.len(), 1);
+        assert_eq!(check.accepted[0].word, "kiwi");
+        cs.add_responses(&alice, None, check.kept());
+        // whoever asked for blank 2 gets it, kiwi has to go in 1
+        let got = cs.check_done(&templates).unwrap();
+        assert!(got.starts_with("t:\n a kiwi and a p"), "{}", got);
+    }
+    #[test]
+    fn credits() {
+        use template::Template;
+        use library::Library;
+        use super::{format_credits, bold_credited};
+        let mut templates = Library::new();
+        let id = templates.add(Template::parse("t: a [noun] [verbed] a [noun]").unwrap());
+        let alice = "alice".to_string();
+        let mut cs = CollectionStatus::new(id, alice.clone());
+        cs.add_responses(&alice, None, vec![Response::new(POS::Verbed, "ate")]);
+        let mut pear = Response::new(POS::Noun, "pear");
+        pear.blank = Some(2);
+        cs.add_responses(&"bob".to_string(), None, vec![pear]);
+        // from before we knew who sent what
+        cs.add_responses(&String::new(), None, vec![Response::new(POS::Noun, "cat")]);
+        let (mut template, credits) = cs.credited(&templates).unwrap();
+        assert_eq!(credits, vec![None, Some(alice.clone()), Some("bob".to_string())]);
+        assert_eq!(format_credits(&template, &credits, |_| true).unwrap(),
+                   "**ate** — @alice\n**pear** — @bob");
+        assert_eq!(format_credits(&template, &credits, |author| author != "bob").unwrap(),
+                   "**ate** — @alice\n**pear** — bob");
+        bold_credited(&mut template, &credits);
+        assert_eq!(template.check_done().unwrap(), "t:\n a cat **ate** a **pear**");
+    }
+    #[test]
+    fn mentions() {
+        use std::collections::HashMap;
+        let (alice, bob, carol) = ("alice".to_string(), "bob".to_string(), "carol".to_string());
+        let mut cs = CollectionStatus::new(0, carol.clone());
+        cs.add_participant(alice.clone());
+        cs.add_participant(bob.clone());
+        let mut prefs = HashMap::new();
+        assert_eq!(cs.to_mention(&prefs), vec![&alice, &bob, &carol]);
+        prefs.insert(bob.clone(), false);
+        prefs.insert(carol.clone(), false);
+        cs.set_mention_pref(&carol, true);
+        cs.set_mention_pref(&alice, false);
+        assert_eq!(cs.to_mention(&prefs), vec![&carol]);
+    }
+    #[test]
+    fn fair_selection() {
+        use template::Template;
+        use library::Library;
+        use rand::{SeedableRng, rngs::StdRng};
+        let mut templates = Library::new();
+        let id = templates.add(Template::parse("t: a [noun] and a [noun] [verbed]").unwrap());
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let carol = "carol".to_string();
+        let mut cs = CollectionStatus::new(id, alice.clone());
+        // alice could fill everything by herself, bob and carol only one blank each
+        cs.add_responses(&alice, None, vec![
+            Response::new(POS::Noun, "a1"),
+            Response::new(POS::Noun, "a2"),
+            Response::new(POS::Verbed, "a3"),
+        ]);
+        cs.add_responses(&bob, None, vec![Response::new(POS::Noun, "b1")]);
+        cs.add_responses(&carol, None, vec![Response::new(POS::Noun, "c1")]);
+        for seed in 0..20 {
+            let mut rng = StdRng::from_seed([seed; 32]);
+            let got = cs.filled_with(&templates, &mut rng).unwrap().0.check_done().unwrap();
+            assert!(got.contains("a3") && got.contains("b1") && got.contains("c1"), "{}", got);
+        }
+    }
+    #[test]
+    fn voting() {
+        use template::Template;
+        use library::Library;
+        use super::Selection;
+        use rand::{SeedableRng, rngs::StdRng};
+        let mut templates = Library::new();
+        let id = templates.add(Template::parse("t: a [noun] and a [verbed]").unwrap());
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let mut cs = CollectionStatus::new(id, alice.clone());
+        cs.set_selection(Selection::Votes);
+        cs.add_responses(&alice, Some(&"1".to_string()), vec![
+            Response::new(POS::Noun, "cat"),
+            Response::new(POS::Verbed, "ran"),
+        ]);
+        cs.add_responses(&bob, Some(&"2".to_string()), vec![Response::new(POS::Noun, "dog")]);
+        let mut sat = Response::new(POS::Verbed, "sat");
+        sat.blank = Some(1);
+        cs.add_responses(&bob, Some(&"3".to_string()), vec![sat]);
+        cs.set_votes(&"1".to_string(), 2);
+        cs.set_votes(&"2".to_string(), 5);
+        cs.set_votes(&"3".to_string(), 1);
+        for seed in 0..10 {
+            let mut rng = StdRng::from_seed([seed; 32]);
+            let got = cs.filled_with(&templates, &mut rng).unwrap().0.check_done().unwrap();
+            assert_eq!(got, "t:\n a dog and a ran");
+        }
+        // a word for one particular blank wins it if it has the most votes
+        cs.set_votes(&"3".to_string(), 3);
+        assert_eq!(cs.check_done(&templates).unwrap(), "t:\n a dog and a sat");
+        // nothing left to ask for, it's up to the votes
+        assert_eq!(cs.progress(&templates).unwrap(),
+                   "every blank has a word, favourite the ones you like best before it closes");
+        cs.remove_status(&"1".to_string());
+        cs.remove_status(&"3".to_string());
+        assert_eq!(cs.progress(&templates).unwrap(), "still need: 1x verbed");
+    }
+    #[test]
+    fn checking_responses() {
+        use template::Template;
+        use library::Library;
+        let mut templates = Library::new();
+        let id = templates.add(Template::parse("t: a [noun] [verbs]").unwrap());
+        let alice = "alice".to_string();
+        let mut cs = CollectionStatus::new(id, alice.clone());
+        cs.add_responses(&alice, None, vec![Response::new(POS::Noun, "cat")]);
+        let check = cs.check_responses(&templates, vec![
+            Response::new(POS::Verbs, "runs"),
+            Response::new(POS::Noun, "dog"),
+            Response::new(POS::Adjective, "red"),
+        ]).unwrap();
+        assert_eq!(check.accepted, vec![Response::new(POS::Verbs, "runs")]);
+        assert_eq!(check.surplus, vec![Response::new(POS::Noun, "dog")]);
+        assert_eq!(check.unneeded, vec![Response::new(POS::Adjective, "red")]);
+        assert_eq!(check.kept().len(), 2);
+        let explained = check.explain().unwrap();
+        assert!(explained.contains("what it has is: 1x noun, 1x verbs"), "{}", explained);
+        let fine = cs.check_responses(&templates, vec![Response::new(POS::Verbs, "runs")]).unwrap();
+        assert_eq!(fine.explain(), None);
+    }
+    #[test]
+    fn tagger_strictness() {
+        use super::tagger_check;
+        use template::Strictness;
+        let resps = vec![
+            Response::new(POS::Verbed, "banana"),
+            Response::new(POS::Verbed, "ate"),
+        ];
+        let tag = |word: &str| if word == "ate" { Some(POS::Verbed) } else { Some(POS::Noun) };
+        let (kept, said) = tagger_check(resps.clone(), Strictness::Off, tag);
+        assert_eq!((kept.len(), said), (2, None));
+        let (kept, said) = tagger_check(resps.clone(), Strictness::Warn, tag);
+        assert_eq!(kept.len(), 2);
+        assert!(said.unwrap().contains("\"banana\" doesn't look like a verbed to me (more like a noun)"));
+        let (kept, said) = tagger_check(resps, Strictness::Reject, tag);
+        assert_eq!(kept, vec![Response::new(POS::Verbed, "ate")]);
+        assert!(said.is_some());
+    }
+    #[test]
+    fn retracting() {
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let mut cs = CollectionStatus::new(0, alice.clone());
+        cs.add_responses(&alice, Some(&"1".to_string()), vec![
+            Response::new(POS::Noun, "cat"),
+            Response::new(POS::Verbs, "runs"),
+        ]);
+        cs.add_responses(&alice, Some(&"2".to_string()), vec![Response::new(POS::Noun, "dog")]);
+        cs.add_responses(&bob, None, vec![Response::new(POS::Noun, "cow")]);
+        // one we couldn't use still gets looked at again, until it's fixed
+        cs.reject_status(&"3".to_string(), &bob);
+        cs.reject_status(&"3".to_string(), &bob);
+        assert_eq!(cs.response_statuses(), vec![
+            ("1".to_string(), alice.clone()),
+            ("2".to_string(), alice.clone()),
+            ("3".to_string(), bob.clone()),
+        ]);
+        assert_eq!(cs.remove_status(&"3".to_string()), 0);
+        assert_eq!(cs.response_statuses().len(), 2);
+        assert_eq!(cs.remove_status(&"1".to_string()), 2);
+        assert_eq!(cs.remove_status(&"1".to_string()), 0);
+        assert_eq!(cs.retract(&alice), 1);
+        assert_eq!(cs.retract(&bob), 1);
+        assert!(cs.resolved.is_empty());
+    }
+    #[test]
+    fn limits() {
+        use chrono::{Duration, Utc};
+        use super::{Limits, ResponseCheck};
+        let alice = "alice".to_string();
+        let mut cs = CollectionStatus::new(0, alice.clone());
+        let three = || ResponseCheck {
+            accepted: vec![Response::new(POS::Noun, "cat")],
+            surplus: vec![Response::new(POS::Noun, "dog"), Response::new(POS::Noun, "cow")],
+            unneeded: vec![Response::new(POS::Verbs, "runs")],
+            ..ResponseCheck::default()
+        };
+        let mut check = three();
+        assert_eq!(cs.limit_responses(&alice, &mut check), None);
+        assert_eq!(check.kept().len(), 3);
+        cs.set_limits(Limits { max_words: Some(2), one_word_each: false, cooldown_minutes: 30 });
+        let mut check = three();
+        let said = cs.limit_responses(&alice, &mut check).unwrap();
+        assert_eq!(said, "you can only have 2 word(s) in this game, so i left out: noun: cow\n\
+                          (!retract takes back what you sent, if you'd rather swap)");
+        // runs was never going to be used, so it doesn't use up a word
+        assert_eq!(check.unneeded.len(), 1);
+        cs.add_responses(&alice, None, check.kept());
+        let mut check = three();
+        cs.limit_responses(&alice, &mut check);
+        assert!(check.kept().is_empty());
+        let mut check = three();
+        cs.limit_responses(&"bob".to_string(), &mut check);
+        assert_eq!(check.kept().len(), 2);
+        let now = Utc::now();
+        assert_eq!(cs.wait_left(&alice, now), None);
+        cs.mark_sent(&alice, now);
+        assert_eq!(cs.wait_left(&alice, now + Duration::minutes(10)), Some(Duration::minutes(20)));
+        assert_eq!(cs.wait_left(&alice, now + Duration::minutes(30)), None);
+        cs.set_limits(Limits { max_words: Some(5), one_word_each: true, cooldown_minutes: 0 });
+        let mut check = three();
+        cs.limit_responses(&"bob".to_string(), &mut check);
+        assert_eq!(check.kept(), vec![Response::new(POS::Noun, "cat")]);
+        assert_eq!(cs.wait_left(&alice, now), None);
+    }
+    #[test]
+    fn limit_rules() {
+        use super::Limits;
+        let mut limits = Limits::default();
+        assert!(limits.add_rule("Up to 3 words each"));
+        assert!(limits.add_rule("wait 30 minutes"));
+        assert_eq!(limits, Limits { max_words: Some(3), one_word_each: false, cooldown_minutes: 30 });
+        // what describe says reads back the same
+        let mut again = Limits::default();
+        for rule in limits.describe().unwrap().split(", ") {
+            assert!(again.add_rule(rule), "{}", rule);
+        }
+        assert_eq!(again, limits);
+        assert!(limits.add_rule("one word each"));
+        assert!(!limits.add_rule("no cheating"));
+        assert!(!limits.add_rule("wait a bit"));
+    }
+    #[test]
+    fn deadlines() {
+        use chrono::{Duration, Utc};
+        let mut cs = CollectionStatus::new(0, "alice".to_string());
+        let now = Utc::now();
+        assert!(!cs.is_expired(now + Duration::weeks(100)));
+        cs.set_default_deadline(Duration::hours(2));
+        assert!(!cs.is_expired(now));
+        assert!(cs.is_expired(now + Duration::hours(3)));
+        // only for games that didn't have one
+        cs.set_default_deadline(Duration::hours(100));
+        assert!(cs.is_expired(now + Duration::hours(3)));
+    }
+    #[test]
+    fn updates() {
+        use chrono::{Duration, Utc};
+        let mut cs = CollectionStatus::new(0, "alice".to_string());
+        let now = Utc::now();
+        assert!(!cs.needs_update(now, Duration::hours(12)));
+        assert!(cs.needs_update(now + Duration::hours(13), Duration::hours(12)));
+        cs.mark_updated(now + Duration::hours(13));
+        assert!(!cs.needs_update(now + Duration::hours(14), Duration::hours(12)));
     }
 }
 
@@ -0,0 +1,683 @@
+// Mentions that start with a !command instead of being a template or a
+// response, e.g. `!show 12`
+
+use pos::*;
+use library::TemplateID;
+use template::{Strictness, Mode};
+use bot::{BotStatus, StatusID, PendingAction};
+use collection::Selection;
+use filter::Filter;
+use stats;
+use AccountID;
+
+use sanitize_all;
+
+// how many templates !weights lists
+const WEIGHTS_SHOWN: usize = 10;
+// and !find
+const FOUND_SHOWN: usize = 10;
+
+const DOC_LINK: &str = "https://github.com/CosineP/madlibs/blob/master/doc/lang-reference.md";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Pos,
+    ListMine,
+    Show(TemplateID),
+    Delete(TemplateID),
+    Strict(TemplateID, Strictness),
+    Mode(TemplateID, Mode),
+    Tag(TemplateID, Vec<String>),
+    Untag(TemplateID, Vec<String>),
+    // see Library::search for what it can be
+    Find(String),
+    // None means the game this was a reply to
+    Status(Option<StatusID>),
+    Retract(Option<StatusID>),
+    Votes(Option<StatusID>),
+    // whether to mention them, in the game this replied to or everywhere
+    Mentions(bool),
+    // the templates most likely to be the next random post
+    Weights,
+    // how the templates and players have done
+    Top,
+    // whether they show up in roundups and !top
+    Leaderboard(bool),
+    // admins only from here on
+    Close(Option<StatusID>),
+    Cancel(Option<StatusID>),
+    Block(AccountID),
+    Unblock(AccountID),
+    Pause,
+    Resume,
+    Post(TemplateID),
+    // the next random post, now instead of whenever it was going to be
+    PostNow,
+    Held,
+    Approve(TemplateID),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnknownCommand(String),
+    ExpectedArgument(&'static str),
+    BadID(String),
+    Blocked,
+}
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::ParseError::*;
+        match self {
+            UnknownCommand(given) => write!(f, "unknown command !{} (try !help)", given),
+            ExpectedArgument(what) => write!(f, "that command needs {} after it", what),
+            BadID(given) => write!(f, "{} isn't a template number", given),
+            // don't repeat the word back, that's the whole point
+            Blocked => write!(f, "one of those tags isn't allowed here"),
+        }
+    }
+}
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+fn parse_acct(arg: Option<&str>) -> Result<AccountID, ParseError> {
+    let arg = arg.ok_or(ParseError::ExpectedArgument("an account"))?;
+    // mentions come to us without the leading @, so match that
+    Ok(arg.trim_start_matches('@').to_string())
+}
+
+fn parse_id(arg: Option<&str>) -> Result<TemplateID, ParseError> {
+    let arg = arg.ok_or(ParseError::ExpectedArgument("a template number"))?;
+    // people will copy the # from our own replies
+    arg.trim_start_matches('#')
+        .parse()
+        .map_err(|_| ParseError::BadID(arg.to_string()))
+}
+
+/// None if this mention isn't a command at all
+pub fn parse_command(status: &str, filter: &Filter) -> Option<Result<Command, ParseError>> {
+    let status = sanitize_all(status);
+    // any @mentions that survived sanitizing come first, skip them
+    let mut words = status.split_whitespace()
+        .skip_while(|word| word.starts_with('@'));
+    let name = words.next()?;
+    if !name.starts_with('!') {
+        return None;
+    }
+    let name = name[1..].to_lowercase();
+    let arg = words.next();
+    let arg2 = words.next();
+    Some(match name.as_str() {
+        "help" => Ok(Command::Help),
+        "pos" => Ok(Command::Pos),
+        "list" => match arg {
+            Some("mine") => Ok(Command::ListMine),
+            _ => Err(ParseError::ExpectedArgument("`mine`")),
+        },
+        "show" => parse_id(arg).map(Command::Show),
+        "delete" => parse_id(arg).map(Command::Delete),
+        "strict" => parse_id(arg).and_then(|id| match arg2 {
+            Some("off") => Ok(Command::Strict(id, Strictness::Off)),
+            Some("warn") => Ok(Command::Strict(id, Strictness::Warn)),
+            Some("reject") => Ok(Command::Strict(id, Strictness::Reject)),
+            _ => Err(ParseError::ExpectedArgument("off, warn or reject")),
+        }),
+        "mode" => parse_id(arg).and_then(|id| match arg2.and_then(Mode::from_name) {
+            Some(mode) => Ok(Command::Mode(id, mode)),
+            None => Err(ParseError::ExpectedArgument("manual, automatic or hybrid")),
+        }),
+        "find" => {
+            let query: Vec<&str> = arg.into_iter().chain(arg2).chain(words.by_ref()).collect();
+            if query.is_empty() {
+                Err(ParseError::ExpectedArgument("something to look for"))
+            } else {
+                Ok(Command::Find(query.join(" ")))
+            }
+        },
+        "tag" | "untag" => parse_id(arg).and_then(|id| {
+            let tags: Vec<String> = arg2.into_iter().chain(words.by_ref()).map(str::to_string).collect();
+            match (tags.is_empty(), name.as_str()) {
+                (true, _) => Err(ParseError::ExpectedArgument("some tags")),
+                // they end up in !show, !find and the roundups
+                (false, "tag") if tags.iter().any(|tag| !filter.allows(tag)) => Err(ParseError::Blocked),
+                (false, "tag") => Ok(Command::Tag(id, tags)),
+                (false, _) => Ok(Command::Untag(id, tags)),
+            }
+        }),
+        "status" => Ok(Command::Status(arg.map(|game| game.to_string()))),
+        "retract" => Ok(Command::Retract(arg.map(|game| game.to_string()))),
+        "votes" => Ok(Command::Votes(arg.map(|game| game.to_string()))),
+        "mentions" => match arg {
+            Some("on") => Ok(Command::Mentions(true)),
+            Some("off") => Ok(Command::Mentions(false)),
+            _ => Err(ParseError::ExpectedArgument("on or off")),
+        },
+        "weights" => Ok(Command::Weights),
+        "top" => Ok(Command::Top),
+        "leaderboard" => match arg {
+            Some("on") => Ok(Command::Leaderboard(true)),
+            Some("off") => Ok(Command::Leaderboard(false)),
+            _ => Err(ParseError::ExpectedArgument("on or off")),
+        },
+        "close" => Ok(Command::Close(arg.map(|game| game.to_string()))),
+        "cancel" => Ok(Command::Cancel(arg.map(|game| game.to_string()))),
+        "block" => parse_acct(arg).map(Command::Block),
+        "unblock" => parse_acct(arg).map(Command::Unblock),
+        "pause" => Ok(Command::Pause),
+        "resume" => Ok(Command::Resume),
+        "post" => match arg {
+            Some(_) => parse_id(arg).map(Command::Post),
+            None => Ok(Command::PostNow),
+        },
+        "held" => Ok(Command::Held),
+        "approve" => parse_id(arg).map(Command::Approve),
+        _ => Err(ParseError::UnknownCommand(name)),
+    })
+}
+
+// the game a command is about: the one it names, or else the one it replied to
+fn open_game<'a>(game: &'a Option<StatusID>, reply_to: Option<&'a StatusID>, bot_status: &BotStatus) -> Result<&'a StatusID, String> {
+    let game_id = match game.as_ref().or(reply_to) {
+        Some(game_id) => game_id,
+        None => return Err("reply to a game with this, or give me its ID".to_string()),
+    };
+    if !bot_status.collection_toots.contains_key(game_id) {
+        return Err(format!("{} isn't a game that's still open", game_id));
+    }
+    Ok(game_id)
+}
+
+impl Command {
+    pub fn needs_admin(&self) -> bool {
+        match self {
+            Command::Close(_)
+                | Command::Cancel(_)
+                | Command::Block(_)
+                | Command::Unblock(_)
+                | Command::Pause
+                | Command::Resume
+                | Command::Post(_)
+                | Command::PostNow
+                | Command::Held
+                | Command::Approve(_) => true,
+            _ => false,
+        }
+    }
+
+    // what admins do with these goes in the audit log
+    pub fn is_audited(&self) -> bool {
+        match self {
+            Command::Delete(_)
+                | Command::Strict(..)
+                | Command::Mode(..)
+                | Command::Tag(..)
+                | Command::Untag(..)
+                | Command::Votes(_) => true,
+            _ => self.needs_admin(),
+        }
+    }
+
+    // Whether the reply repeats what got a template held, in which case it
+    // goes to the admin as a DM instead of being posted where anyone can see
+    // it. Check before execute, !approve and !delete change what's held
+    pub fn shows_held(&self, is_admin: bool, bot_status: &BotStatus) -> bool {
+        if !is_admin {
+            return false;
+        }
+        let templates = &bot_status.known_templates;
+        match self {
+            Command::Held => true,
+            Command::Show(id) => templates.get(*id).map_or(false, |template| template.held),
+            Command::Find(query) => templates.search(query).iter().any(|(_, template)| template.held),
+            _ => false,
+        }
+    }
+
+    /// Does whatever the command says and returns what to reply with
+    /// Anything that needs to post goes in bot_status.pending for the bot
+    pub fn execute(&self, acct: &AccountID, is_admin: bool, reply_to: Option<&StatusID>, bot_status: &mut BotStatus) -> String {
+        if self.needs_admin() && !is_admin {
+            return "only admins can do that".to_string();
+        }
+        match self {
+            Command::Help => format!("mention me with a template like `my [noun] [verbs] at night` \
+                and i'll fill it in from my timeline. give it a title like `title: ...` \
+                and everyone can play instead
+
+commands:
+!pos: the words you can use in [brackets]
+!list mine: templates you sent
+!show <number>: one template
+!delete <number>: forget a template you sent
+!strict <number> off|warn|reject: whether to check words people send for it
+!mode <number> manual|automatic|hybrid: how it gets played
+!tag <number> <tags>: tag a template you sent (!untag to take them off)
+!find <words>: templates with those words, or tag:poetry, by:account, lang:en
+!status <game>: what a game still needs (or reply to the game with just !status)
+!retract <game>: take back the words you sent a game (same as !status for which one)
+!votes <game>: let favourites pick the words in a game you started
+!mentions on|off: whether i cc you about games (reply to one for just that game)
+!weights: the templates i'm most likely to post next
+!top: which templates and players get the most favourites
+!leaderboard on|off: whether you show up in that and the roundups
+
+everything else: {}", DOC_LINK),
+            Command::Pos => {
+                let mut text = String::from("the words you can use in [brackets]:\n");
+                for (pos, example) in EXAMPLES.iter() {
+                    text.push_str(&format!("\n{}: {}", pos_to_str(pos), example));
+                }
+                text
+            },
+            Command::ListMine => {
+                let mine: Vec<_> = bot_status.known_templates.iter()
+                    .filter(|(_, template)| template.author.as_ref() == Some(acct))
+                    .map(|(id, template)| if template.held {
+                        // this is a public reply, it can't repeat what tripped the filter
+                        format!("#{}: (held for review)", id)
+                    } else {
+                        format!("#{}: {}", id, template.summary())
+                    })
+                    .collect();
+                if mine.is_empty() {
+                    "you haven't sent me any templates (that i know of)".to_string()
+                } else {
+                    format!("your templates:\n\n{}", mine.join("\n"))
+                }
+            },
+            Command::Show(id) => match bot_status.known_templates.get(*id) {
+                Some(template) if template.held && !is_admin => {
+                    format!("template #{} is held for review", id)
+                },
+                Some(template) if template.tags.is_empty() => {
+                    format!("#{}, sent {} time(s):\n\n{}", id, template.submissions, template.source())
+                },
+                Some(template) => format!("#{}, sent {} time(s), tagged {}:\n\n{}",
+                                          id, template.submissions, template.tags.join(", "),
+                                          template.source()),
+                None => format!("there's no template #{}", id),
+            },
+            Command::Delete(id) => {
+                match bot_status.known_templates.get(*id) {
+                    None => return format!("there's no template #{}", id),
+                    Some(template) if !is_admin && template.author.as_ref() != Some(acct) => {
+                        return format!("template #{} isn't yours to delete", id);
+                    },
+                    Some(_) => (),
+                }
+                if bot_status.collection_toots.values().any(|game| game.template_id() == *id) {
+                    return format!("template #{} is in a game that's still open", id);
+                }
+                bot_status.known_templates.remove(*id);
+                format!("deleted template #{}", id)
+            },
+            Command::Strict(id, strictness) => match bot_status.known_templates.get_mut(*id) {
+                None => format!("there's no template #{}", id),
+                Some(ref template) if !is_admin && template.author.as_ref() != Some(acct) => {
+                    format!("template #{} isn't yours to change", id)
+                },
+                Some(template) => {
+                    template.strictness = *strictness;
+                    match strictness {
+                        Strictness::Off => format!("template #{} takes any word now", id),
+                        Strictness::Warn => format!("template #{} will warn about words that \
+                                                     don't look right", id),
+                        Strictness::Reject => format!("template #{} will leave out words that \
+                                                       don't look right", id),
+                    }
+                },
+            },
+            Command::Mode(id, mode) => match bot_status.known_templates.get_mut(*id) {
+                None => format!("there's no template #{}", id),
+                Some(ref template) if !is_admin && template.author.as_ref() != Some(acct) => {
+                    format!("template #{} isn't yours to change", id)
+                },
+                Some(template) => {
+                    template.mode = Some(*mode);
+                    match mode {
+                        Mode::Manual => format!("template #{} will be played by everyone", id),
+                        Mode::Automatic => format!("template #{} will be filled in from my timeline", id),
+                        Mode::Hybrid => format!("template #{} will be played by everyone, and \
+                                                 whatever's left gets filled in from my timeline", id),
+                    }
+                },
+            },
+            Command::Find(query) => {
+                let found: Vec<String> = bot_status.known_templates.search(query).into_iter()
+                    // a match could be on the word that got it held
+                    .filter(|(_, template)| is_admin || !template.held)
+                    .map(|(id, template)| format!("#{}: {}", id, template.summary()))
+                    .collect();
+                match found.len() {
+                    0 => "no templates match that".to_string(),
+                    n if n > FOUND_SHOWN => format!("the newest {} of {}:\n\n{}", FOUND_SHOWN, n,
+                                                    found[..FOUND_SHOWN].join("\n")),
+                    _ => found.join("\n"),
+                }
+            },
+            Command::Tag(id, tags) | Command::Untag(id, tags) => match bot_status.known_templates.get_mut(*id) {
+                None => format!("there's no template #{}", id),
+                Some(ref template) if !is_admin && template.author.as_ref() != Some(acct) => {
+                    format!("template #{} isn't yours to change", id)
+                },
+                Some(template) => {
+                    if let Command::Tag(..) = self {
+                        template.add_tags(tags);
+                    } else {
+                        template.remove_tags(tags);
+                    }
+                    if template.tags.is_empty() {
+                        format!("template #{} doesn't have any tags now", id)
+                    } else {
+                        format!("template #{} is tagged {} now", id, template.tags.join(", "))
+                    }
+                },
+            },
+            Command::Status(game) => {
+                let game_id = match open_game(game, reply_to, bot_status) {
+                    Ok(game_id) => game_id,
+                    Err(text) => return text,
+                };
+                let collection = &bot_status.collection_toots[game_id];
+                let templates = &bot_status.known_templates;
+                let title = templates.get(collection.template_id())
+                    .and_then(|template| template.title.clone())
+                    .unwrap_or_else(|| "Untitled".to_string());
+                let progress = collection.progress(templates)
+                    .unwrap_or_else(|| "its template was deleted".to_string());
+                let mut text = format!("**{}** (template #{})\n{}\n{} people playing",
+                                       title, collection.template_id(), progress,
+                                       collection.participant_count());
+                if let Some(deadline) = collection.deadline() {
+                    let hours = (deadline - chrono::Utc::now()).num_hours().max(0);
+                    text.push_str(&format!("\ncloses in about {} hours", hours));
+                }
+                text
+            },
+            Command::Retract(game) => {
+                let game_id = match open_game(game, reply_to, bot_status) {
+                    Ok(game_id) => game_id.clone(),
+                    Err(text) => return text,
+                };
+                let collection = bot_status.collection_toots.get_mut(&game_id).unwrap();
+                match collection.retract(acct) {
+                    0 => "you haven't sent that game any words".to_string(),
+                    count => format!("took back the {} word(s) you sent, \
+                                      reply to the game again if you want to send new ones", count),
+                }
+            },
+            Command::Votes(game) => {
+                let game_id = match open_game(game, reply_to, bot_status) {
+                    Ok(game_id) => game_id.clone(),
+                    Err(text) => return text,
+                };
+                let collection = bot_status.collection_toots.get_mut(&game_id).unwrap();
+                if !is_admin && collection.starter() != Some(acct) {
+                    return "only whoever started the game can change how it's picked".to_string();
+                }
+                collection.set_selection(Selection::Votes);
+                "this game is picked by votes now: favourite the replies you like best, \
+                 the words with the most favourites win when it closes".to_string()
+            },
+            Command::Mentions(wants) => {
+                let game = reply_to.and_then(|game_id| bot_status.collection_toots.get_mut(game_id));
+                let verb = if *wants { "will" } else { "won't" };
+                match game {
+                    Some(collection) => {
+                        collection.set_mention_pref(acct, *wants);
+                        format!("i {} cc you about this game", verb)
+                    },
+                    None => {
+                        bot_status.mention_prefs.insert(acct.clone(), *wants);
+                        format!("i {} cc you about games (unless you say otherwise in one)", verb)
+                    },
+                }
+            },
+            Command::Weights => {
+                let mut weights = bot_status.known_templates.weights(chrono::Utc::now());
+                let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+                if weights.is_empty() || total <= 0.0 {
+                    return "i don't know any templates i can post yet".to_string();
+                }
+                weights.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+                let lines: Vec<String> = weights.iter().take(WEIGHTS_SHOWN)
+                    .map(|(id, weight)| {
+                        let stats = &bot_status.known_templates.get(*id).unwrap().stats;
+                        format!("#{}: {:.1}% (posted {} times, {} favourites, {} boosts)",
+                                id, weight / total * 100.0, stats.posted, stats.favourites, stats.boosts)
+                    })
+                    .collect();
+                format!("most likely to be next:\n\n{}", lines.join("\n"))
+            },
+            Command::Top => stats::report(&bot_status.known_templates, &bot_status.posts,
+                                          &bot_status.player_totals, &bot_status.off_leaderboard),
+            Command::Leaderboard(true) => {
+                bot_status.off_leaderboard.remove(acct);
+                "you're back on the leaderboard".to_string()
+            },
+            Command::Leaderboard(false) => {
+                bot_status.off_leaderboard.insert(acct.clone());
+                "you're off the leaderboard, your words still count in games".to_string()
+            },
+            Command::Close(game) | Command::Cancel(game) => {
+                let game_id = match open_game(game, reply_to, bot_status) {
+                    Ok(game_id) => game_id.clone(),
+                    Err(text) => return text,
+                };
+                if let Command::Close(_) = self {
+                    bot_status.pending.push(PendingAction::Close(game_id.clone()));
+                    format!("closing {}, the rest of it gets filled from my timeline", game_id)
+                } else {
+                    bot_status.pending.push(PendingAction::Cancel(game_id.clone()));
+                    format!("cancelling {}", game_id)
+                }
+            },
+            Command::Block(blocked) => {
+                bot_status.blocked.insert(blocked.clone());
+                format!("ignoring everything from @{} now", blocked)
+            },
+            Command::Unblock(blocked) => {
+                if bot_status.blocked.remove(blocked) {
+                    format!("listening to @{} again", blocked)
+                } else {
+                    format!("@{} wasn't blocked", blocked)
+                }
+            },
+            Command::Pause => {
+                bot_status.paused = true;
+                "random posts are paused until !resume".to_string()
+            },
+            Command::Resume => {
+                bot_status.paused = false;
+                "random posts are back on".to_string()
+            },
+            Command::Post(id) => {
+                if bot_status.known_templates.get(*id).is_none() {
+                    return format!("there's no template #{}", id);
+                }
+                bot_status.pending.push(PendingAction::Post(*id));
+                format!("posting template #{}", id)
+            },
+            Command::PostNow => {
+                if bot_status.paused {
+                    return "random posts are paused, !resume them first".to_string();
+                }
+                // quiet hours don't count, someone asked for this one
+                bot_status.next_random = Some(chrono::Utc::now());
+                "posting the next random post now".to_string()
+            },
+            Command::Held => {
+                let held: Vec<_> = bot_status.known_templates.iter()
+                    .filter(|(_, template)| template.held)
+                    .map(|(id, template)| format!("#{}: {}", id, template.summary()))
+                    .collect();
+                if held.is_empty() {
+                    "nothing is held for review".to_string()
+                } else {
+                    format!("held for review (!approve or !delete them):\n\n{}", held.join("\n"))
+                }
+            },
+            Command::Approve(id) => match bot_status.known_templates.get_mut(*id) {
+                Some(template) => {
+                    template.held = false;
+                    format!("approved template #{}", id)
+                },
+                None => format!("there's no template #{}", id),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_command, Command, ParseError};
+    use filter::Filter;
+    use template::{Strictness, Mode};
+    use bot::{BotStatus, PendingAction};
+    use collection::{CollectionStatus, Response};
+    use pos::POS;
+    use template::Template;
+    #[test]
+    fn parse() {
+        assert_eq!(parse_command("<a href=x>@madlibs</a> !help", &Filter::default()), Some(Ok(Command::Help)));
+        assert_eq!(parse_command("@madlibs !show #12", &Filter::default()), Some(Ok(Command::Show(12))));
+        assert_eq!(parse_command("!LIST mine", &Filter::default()), Some(Ok(Command::ListMine)));
+        assert_eq!(parse_command("!status", &Filter::default()), Some(Ok(Command::Status(None))));
+        assert_eq!(parse_command("!strict 3 reject", &Filter::default()), Some(Ok(Command::Strict(3, Strictness::Reject))));
+        assert_eq!(parse_command("!strict 3", &Filter::default()),
+                   Some(Err(ParseError::ExpectedArgument("off, warn or reject"))));
+        assert_eq!(parse_command("!mode 3 auto", &Filter::default()), Some(Ok(Command::Mode(3, Mode::Automatic))));
+        assert_eq!(parse_command("!mode 3 sometimes", &Filter::default()),
+                   Some(Err(ParseError::ExpectedArgument("manual, automatic or hybrid"))));
+        assert_eq!(parse_command("!block @spam@bad.example", &Filter::default()),
+                   Some(Ok(Command::Block("spam@bad.example".to_string()))));
+        assert_eq!(parse_command("!delete twelve", &Filter::default()),
+                   Some(Err(ParseError::BadID("twelve".to_string()))));
+        assert_eq!(parse_command("!post", &Filter::default()), Some(Ok(Command::PostNow)));
+        assert_eq!(parse_command("!leaderboard off", &Filter::default()), Some(Ok(Command::Leaderboard(false))));
+        assert_eq!(parse_command("!tag 3 spooky poetry", &Filter::default()),
+                   Some(Ok(Command::Tag(3, vec!["spooky".to_string(), "poetry".to_string()]))));
+        assert_eq!(parse_command("!untag 3", &Filter::default()), Some(Err(ParseError::ExpectedArgument("some tags"))));
+        let filter = Filter::new(vec!["grr"]);
+        assert_eq!(parse_command("!tag 3 spooky Grr!", &filter), Some(Err(ParseError::Blocked)));
+        assert!(parse_command("!untag 3 grr", &filter).unwrap().is_ok());
+        assert_eq!(parse_command("!find fruit  by:alice #food", &Filter::default()),
+                   Some(Ok(Command::Find("fruit by:alice #food".to_string()))));
+        assert_eq!(parse_command("!dance", &Filter::default()),
+                   Some(Err(ParseError::UnknownCommand("dance".to_string()))));
+        assert_eq!(parse_command("my [noun] says !hi", &Filter::default()), None);
+    }
+    #[test]
+    fn delete_only_your_own() {
+        let mut bot_status = BotStatus::new();
+        let mut template = Template::parse("a [noun]").unwrap();
+        template.author = Some("alice".to_string());
+        let id = bot_status.known_templates.add(template);
+        let bob = "bob".to_string();
+        let alice = "alice".to_string();
+        Command::Delete(id).execute(&bob, false, None, &mut bot_status);
+        assert!(bot_status.known_templates.get(id).is_some());
+        bot_status.collection_toots.insert("1".to_string(), CollectionStatus::new(id, bob.clone()));
+        Command::Delete(id).execute(&alice, false, None, &mut bot_status);
+        assert!(bot_status.known_templates.get(id).is_some());
+        bot_status.collection_toots.clear();
+        // admins can delete anyone's
+        Command::Delete(id).execute(&bob, true, None, &mut bot_status);
+        assert!(bot_status.known_templates.get(id).is_none());
+    }
+    #[test]
+    fn admin_only() {
+        let mut bot_status = BotStatus::new();
+        let id = bot_status.known_templates.add(Template::parse("a [noun]").unwrap());
+        let bob = "bob".to_string();
+        Command::Pause.execute(&bob, false, None, &mut bot_status);
+        Command::Post(id).execute(&bob, false, None, &mut bot_status);
+        Command::Block("alice".to_string()).execute(&bob, false, None, &mut bot_status);
+        assert!(!bot_status.paused);
+        assert!(bot_status.pending.is_empty());
+        assert!(bot_status.blocked.is_empty());
+        Command::Pause.execute(&bob, true, None, &mut bot_status);
+        Command::Post(id).execute(&bob, true, None, &mut bot_status);
+        Command::Block("alice".to_string()).execute(&bob, true, None, &mut bot_status);
+        assert!(bot_status.paused);
+        assert_eq!(bot_status.pending, vec![PendingAction::Post(id)]);
+        assert!(bot_status.blocked.contains("alice"));
+        // not while it's paused, it'd only get skipped
+        Command::PostNow.execute(&bob, true, None, &mut bot_status);
+        assert!(bot_status.next_random.is_none());
+        Command::Resume.execute(&bob, true, None, &mut bot_status);
+        Command::PostNow.execute(&bob, true, None, &mut bot_status);
+        assert!(bot_status.next_random.is_some());
+    }
+    #[test]
+    fn held_stays_private() {
+        let mut bot_status = BotStatus::new();
+        let mut template = Template::parse("a [noun] of badword").unwrap();
+        template.held = true;
+        let held = bot_status.known_templates.add(template);
+        let fine = bot_status.known_templates.add(Template::parse("a [noun] of cake").unwrap());
+        assert!(Command::Held.shows_held(true, &bot_status));
+        assert!(Command::Show(held).shows_held(true, &bot_status));
+        assert!(!Command::Show(fine).shows_held(true, &bot_status));
+        assert!(Command::Find("badword".to_string()).shows_held(true, &bot_status));
+        assert!(!Command::Find("cake".to_string()).shows_held(true, &bot_status));
+        // they don't get to see it at all
+        assert!(!Command::Show(held).shows_held(false, &bot_status));
+    }
+    #[test]
+    fn status_of_game() {
+        let mut bot_status = BotStatus::new();
+        let id = bot_status.known_templates.add(
+            Template::parse("game: a [noun] and a [noun], [verbed]").unwrap());
+        let mut game = CollectionStatus::new(id, "alice".to_string());
+        game.add_responses(&"alice".to_string(), None, vec![Response::new(POS::Noun, "cat")]);
+        bot_status.collection_toots.insert("1".to_string(), game);
+        let got = Command::Status(None).execute(&"bob".to_string(), false, Some(&"1".to_string()), &mut bot_status);
+        assert!(got.contains("still need: 1x noun, 1x verbed"), "{}", got);
+        let got = Command::Retract(None).execute(&"bob".to_string(), false, Some(&"1".to_string()), &mut bot_status);
+        assert_eq!(got, "you haven't sent that game any words");
+        Command::Retract(Some("1".to_string())).execute(&"alice".to_string(), false, None, &mut bot_status);
+        let got = Command::Status(None).execute(&"bob".to_string(), false, Some(&"1".to_string()), &mut bot_status);
+        assert!(got.contains("still need: 2x noun, 1x verbed"), "{}", got);
+    }
+    #[test]
+    fn mention_prefs() {
+        let mut bot_status = BotStatus::new();
+        let alice = "alice".to_string();
+        bot_status.collection_toots.insert("1".to_string(), CollectionStatus::new(0, alice.clone()));
+        assert_eq!(parse_command("!mentions off", &Filter::default()), Some(Ok(Command::Mentions(false))));
+        assert!(parse_command("!mentions", &Filter::default()).unwrap().is_err());
+        Command::Mentions(false).execute(&alice, false, None, &mut bot_status);
+        assert!(!bot_status.wants_mention(&alice));
+        Command::Mentions(true).execute(&alice, false, Some(&"1".to_string()), &mut bot_status);
+        assert!(!bot_status.wants_mention(&alice));
+        let game = &bot_status.collection_toots["1"];
+        assert_eq!(game.to_mention(&bot_status.mention_prefs), vec![&alice]);
+    }
+    #[test]
+    fn switch_to_votes() {
+        use collection::Selection;
+        let mut bot_status = BotStatus::new();
+        let mut template = Template::parse("game: a [noun]").unwrap();
+        template.author = Some("alice".to_string());
+        let id = bot_status.known_templates.add(template);
+        // bob sent it again, so it's his game, not alice's
+        bot_status.collection_toots.insert("1".to_string(), CollectionStatus::new(id, "bob".to_string()));
+        bot_status.collection_toots.insert("2".to_string(), CollectionStatus::for_template(id));
+        assert_eq!(parse_command("!votes", &Filter::default()), Some(Ok(Command::Votes(None))));
+        let game = Some(&"1".to_string());
+        Command::Votes(None).execute(&"alice".to_string(), false, game, &mut bot_status);
+        assert_eq!(bot_status.collection_toots["1"].selection(), Selection::Fair);
+        Command::Votes(None).execute(&"bob".to_string(), false, game, &mut bot_status);
+        assert_eq!(bot_status.collection_toots["1"].selection(), Selection::Votes);
+        // nobody started the bot's own games, so only admins can
+        let game = Some(&"2".to_string());
+        Command::Votes(None).execute(&"alice".to_string(), false, game, &mut bot_status);
+        assert_eq!(bot_status.collection_toots["2"].selection(), Selection::Fair);
+        Command::Votes(None).execute(&"carol".to_string(), true, game, &mut bot_status);
+        assert_eq!(bot_status.collection_toots["2"].selection(), Selection::Votes);
+    }
+}
@@ -0,0 +1,248 @@
+// Settings the operator picks, as opposed to BotStatus, which is what the bot
+// keeps track of itself. Lives in config.toml, and everything in it is optional
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use chrono::{DateTime, Datelike, Utc};
+
+use AccountID;
+use filter::Filter;
+use collection::{Selection, Limits};
+use template::{Template, Strictness, Mode};
+use scheduler::{Policy, Schedule, Pool};
+
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Acknowledge {
+    Favourite,
+    // says what the game still needs
+    Reply,
+    Off,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Credit {
+    Off,
+    // bolds the words people sent and lists who sent them under the result
+    Post,
+    // the same list, but in a reply of its own
+    Reply,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Roundup {
+    Off,
+    // the best madlibs and players since the last one, every monday (UTC)
+    Weekly,
+    // or on the 1st of the month
+    Monthly,
+}
+
+impl Roundup {
+    pub fn is_due(&self, last: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        match self {
+            Roundup::Off => false,
+            Roundup::Weekly => now.iso_week() != last.iso_week(),
+            Roundup::Monthly => (now.year(), now.month()) != (last.year(), last.month()),
+        }
+    }
+
+    // what the post calls it, "the best madlibs this week"
+    pub fn period(&self) -> &'static str {
+        match self {
+            Roundup::Monthly => "month",
+            _ => "week",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    // full handles, the same way they show up in mentions (user@domain, or
+    // just user for accounts on our own instance)
+    pub admins: HashSet<AccountID>,
+    // words we never post, on top of whatever's in blocklist_file
+    pub blocklist: Vec<String>,
+    // one word per line, # for comments
+    pub blocklist_file: Option<String>,
+    // how new games pick between words when too many come in
+    pub selection: Selection,
+    // how much of a new game one person can fill in, a [limits] table
+    pub limits: Limits,
+    // how long a manual game stays open
+    pub game_hours: i64,
+    // how long people get with a hybrid game before the timeline takes over
+    pub hybrid_hours: i64,
+    // when a game runs out of time: true fills the rest from the timeline,
+    // false just says it's closed unfinished
+    pub fill_on_deadline: bool,
+    // titled templates past this many open games get played automatically
+    pub max_open_games: usize,
+    // what we do when someone's words make it into a game
+    pub acknowledge: Acknowledge,
+    // who gets named for which word once a game is done
+    pub credit: Credit,
+    // most people to cc in one toot, the rest get their own replies
+    pub max_mentions: usize,
+    // how often an open game gets a "still need" reply and a reboost
+    pub update_hours: i64,
+    // what the bot's own posts do, a [policy] table
+    pub policy: Policy,
+    // when the bot's own posts can go out, a [schedule] table
+    pub schedule: Schedule,
+    // how often to post the best madlibs and a leaderboard
+    pub roundup: Roundup,
+    // tagged templates that get played instead of the rest some of the year
+    pub pools: Vec<Pool>,
+    // the hour (in the schedule's timezone) a template of the day game
+    // opens each day, None for no template of the day
+    pub daily_hour: Option<u32>,
+    // what new templates do with manual responses the tagger disagrees with
+    pub default_strictness: Strictness,
+    // built from blocklist and blocklist_file when loading
+    #[serde(skip)]
+    pub filter: Filter,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            admins: HashSet::new(),
+            blocklist: Vec::new(),
+            blocklist_file: None,
+            selection: Selection::default(),
+            limits: Limits::default(),
+            game_hours: 48,
+            hybrid_hours: 6,
+            fill_on_deadline: true,
+            max_open_games: 5,
+            acknowledge: Acknowledge::Favourite,
+            credit: Credit::Off,
+            max_mentions: 10,
+            update_hours: 12,
+            policy: Policy::default(),
+            schedule: Schedule::default(),
+            roundup: Roundup::Off,
+            pools: Vec::new(),
+            daily_hour: None,
+            default_strictness: Strictness::default(),
+            filter: Filter::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut text = String::new();
+        match File::open(CONFIG_FILE) {
+            Ok(mut file) => {
+                file.read_to_string(&mut text)
+                    .expect("couldn't read config file");
+            },
+            Err(_) => {
+                info!("no {}, using the defaults", CONFIG_FILE);
+                return Config::default();
+            }
+        }
+        let mut config: Config = toml::from_str(&text).expect("couldn't parse config file");
+        config.build_filter();
+        config
+    }
+
+    fn build_filter(&mut self) {
+        let mut words = self.blocklist.clone();
+        if let Some(path) = &self.blocklist_file {
+            let mut text = String::new();
+            File::open(path)
+                .and_then(|mut file| file.read_to_string(&mut text))
+                .expect("couldn't read blocklist file");
+            words.append(&mut Filter::parse_wordlist(&text));
+        }
+        self.filter = Filter::new(words);
+        info!("filtering {} words", self.filter.len());
+    }
+
+    pub fn is_admin(&self, acct: &str) -> bool {
+        self.admins.contains(acct)
+    }
+
+    // how long a game in that mode stays open
+    pub fn game_length(&self, mode: Mode) -> chrono::Duration {
+        match mode {
+            Mode::Hybrid => chrono::Duration::hours(self.hybrid_hours),
+            _ => chrono::Duration::hours(self.game_hours),
+        }
+    }
+
+    // the rules for a game of it: its own if it has any, otherwise ours
+    pub fn game_limits(&self, template: &Template) -> Limits {
+        template.limits.unwrap_or(self.limits)
+    }
+
+    // the tags of the pools that are on right now
+    pub fn active_tags(&self, now: DateTime<Utc>) -> Vec<&str> {
+        let today = self.schedule.local(now).date();
+        self.pools.iter()
+            .filter(|pool| pool.contains(today))
+            .map(|pool| pool.tag.as_str())
+            .collect()
+    }
+
+    pub fn update_interval(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.update_hours)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Config, Roundup};
+    use chrono::{DateTime, Utc};
+    #[test]
+    fn partial_config() {
+        let config: Config = ::toml::from_str(r#"admins = ["cosine@anticapitalist.party"]"#).unwrap();
+        assert!(config.is_admin("cosine@anticapitalist.party"));
+        assert!(!config.is_admin("cosine"));
+        let empty: Config = ::toml::from_str("").unwrap();
+        assert!(empty.admins.is_empty());
+        assert_eq!(empty.game_hours, 48);
+        let limited: Config = ::toml::from_str("[limits]\nmax_words = 3").unwrap();
+        assert_eq!(limited.limits.max_words, Some(3));
+        assert_eq!(limited.limits.cooldown_minutes, 0);
+    }
+    #[test]
+    fn roundups() {
+        let utc = |time| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+        // a sunday, the monday after, and the 1st of the next month
+        let sunday = utc("2020-01-26T23:00:00Z");
+        let monday = utc("2020-01-27T00:30:00Z");
+        let first = utc("2020-02-01T09:00:00Z");
+        assert!(Roundup::Weekly.is_due(sunday, monday));
+        assert!(!Roundup::Weekly.is_due(monday, first));
+        assert!(!Roundup::Monthly.is_due(sunday, monday));
+        assert!(Roundup::Monthly.is_due(monday, first));
+        assert!(!Roundup::Off.is_due(sunday, first));
+    }
+    #[test]
+    fn pools() {
+        let config: Config = ::toml::from_str(r#"
+            daily_hour = 9
+            [schedule]
+            timezone = "Europe/Berlin"
+            [[pools]]
+            tag = "halloween"
+            from = "10-1"
+            until = "10-31"
+        "#).unwrap();
+        assert_eq!(config.daily_hour, Some(9));
+        let utc = |time| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+        // already november where the bot is
+        assert_eq!(config.active_tags(utc("2020-10-31T23:30:00Z")), Vec::<&str>::new());
+        assert_eq!(config.active_tags(utc("2020-10-31T12:00:00Z")), vec!["halloween"]);
+    }
+}
@@ -0,0 +1,72 @@
+// Words we refuse to post, wherever they'd come from: the timeline, a manual
+// response, or a template
+
+use std::collections::HashSet;
+
+#[derive(Default, Debug)]
+pub struct Filter {
+    words: HashSet<String>,
+}
+
+// Compares words without case or punctuation, so "Slur!" is caught by "slur"
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+impl Filter {
+    pub fn new<I, S>(words: I) -> Self
+            where I: IntoIterator<Item = S>, S: AsRef<str> {
+        let words = words.into_iter()
+            .map(|word| normalize(word.as_ref()))
+            .filter(|word| word != "")
+            .collect();
+        Filter { words }
+    }
+
+    // Parses a wordlist file: one word per line, # starts a comment
+    pub fn parse_wordlist(text: &str) -> Vec<String> {
+        text.lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| *line != "")
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn allows_word(&self, word: &str) -> bool {
+        !self.words.contains(&normalize(word))
+    }
+
+    // true if no word in it is blocked; matches whole words only, so we
+    // don't end up blocking "class" over "ass"
+    pub fn allows(&self, text: &str) -> bool {
+        text.split(|c: char| !c.is_alphanumeric())
+            .all(|word| self.allows_word(word))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Filter;
+    #[test]
+    fn whole_words() {
+        let filter = Filter::new(Filter::parse_wordlist("# our list\nBadword\n\n  worse # really\n"));
+        assert_eq!(filter.len(), 2);
+        assert!(!filter.allows_word("badword"));
+        assert!(!filter.allows_word("BADWORD!"));
+        assert!(filter.allows_word("badwords"));
+        assert!(!filter.allows("a [noun] and a worse [noun]"));
+        assert!(!filter.allows("title:badword"));
+        assert!(filter.allows("a [noun] and a worsening [noun]"));
+    }
+}
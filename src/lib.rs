@@ -5,11 +5,14 @@ extern crate log;
 extern crate simple_logger;
 extern crate elefren;
 extern crate chrono;
+extern crate chrono_tz;
 extern crate toml;
 // Yes, it is worth it for both. TOML doesn't support Vec<Template>,
 // and elefren doesn't support anything but TOML
-// TODO: I could technically just serialize the credentials to bincode as well
-extern crate bincode;
+// (bincode used to be the other one, but you couldn't add a field to anything
+// without breaking everyone's status file)
+// TODO: I could technically just serialize the credentials to JSON as well
+extern crate serde_json;
 extern crate rand;
 extern crate senna;
 extern crate regex;
@@ -20,8 +23,14 @@ extern crate lazy_static;
 extern crate bimap;
 
 pub mod pos;
+pub mod config;
+pub mod filter;
 pub mod template;
+pub mod library;
+pub mod stats;
 pub mod collection;
+pub mod command;
+pub mod scheduler;
 pub mod bot;
 
 // We're gonna store the full handle[@domain] so we can mention, ID can't
@@ -43,6 +52,13 @@ fn sanitize_all(status: &str) -> String {
     // Remove *anything else* in TRUE <> charaters, stripping html
     let re = Regex::new(r"<[^<]*>").unwrap();
     let status = re.replace_all(&status, "");
-    status.to_string()
+    // Now that the tags are gone, the text can have its quotes and brackets
+    // back. &amp; goes last so &amp;lt; comes out as &lt;
+    status.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
 }
 
@@ -0,0 +1,268 @@
+// Keeps every template we know about under an ID that never changes, so
+// collections (and anything else) can point at one safely
+
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+
+use template::Template;
+
+pub type TemplateID = u64;
+
+// What happened to a template someone sent us
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Submitted {
+    New(TemplateID),
+    Duplicate(TemplateID),
+    NearDuplicate(TemplateID),
+}
+impl Submitted {
+    pub fn id(&self) -> TemplateID {
+        match *self {
+            Submitted::New(id)
+                | Submitted::Duplicate(id)
+                | Submitted::NearDuplicate(id) => id,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Library {
+    // only ever goes up, so a removed template's ID is never handed out again
+    next_id: TemplateID,
+    templates: BTreeMap<TemplateID, Template>,
+}
+
+impl Library {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, template: Template) -> TemplateID {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.templates.insert(id, template);
+        id
+    }
+
+    // Like add, but if we already have this template (or close enough) it
+    // just counts another submission of the one we have. held ones don't
+    // count, a clean template shouldn't get played as one that isn't
+    pub fn submit(&mut self, template: Template) -> Submitted {
+        let normalized = template.normalized();
+        let mut near = None;
+        for (id, known) in self.templates.iter().filter(|(_, known)| !known.held) {
+            if known.normalized() == normalized {
+                near = Some(Submitted::Duplicate(*id));
+                break;
+            }
+            if near.is_none() && known.is_near_duplicate(&template) {
+                near = Some(Submitted::NearDuplicate(*id));
+            }
+        }
+        match near {
+            Some(submitted) => {
+                let known = self.templates.get_mut(&submitted.id()).unwrap();
+                known.submissions += template.submissions;
+                // a title is worth keeping, it's what lets it run manually
+                if known.title.is_none() {
+                    known.title = template.title;
+                }
+                submitted
+            },
+            None => Submitted::New(self.add(template)),
+        }
+    }
+
+    // Folds another library (an import, say) into this one, returning where
+    // each of its templates ended up
+    pub fn merge(&mut self, other: Library) -> BTreeMap<TemplateID, TemplateID> {
+        let mut moved = BTreeMap::new();
+        for (old_id, template) in other.templates {
+            moved.insert(old_id, self.submit(template).id());
+        }
+        moved
+    }
+
+    pub fn get(&self, id: TemplateID) -> Option<&Template> {
+        self.templates.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: TemplateID) -> Option<&mut Template> {
+        self.templates.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: TemplateID) -> Option<Template> {
+        self.templates.remove(&id)
+    }
+
+    pub fn ids(&self) -> Vec<TemplateID> {
+        self.templates.keys().cloned().collect()
+    }
+
+    // everything that isn't held for review
+    pub fn playable_ids(&self) -> Vec<TemplateID> {
+        self.templates.iter()
+            .filter(|(_, template)| !template.held)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn mark_posted(&mut self, id: TemplateID, now: DateTime<Utc>) {
+        if let Some(template) = self.templates.get_mut(&id) {
+            template.stats.mark_posted(now);
+        }
+    }
+
+    // every playable template with how likely it is to be the random one,
+    // see Stats::weight
+    pub fn weights(&self, now: DateTime<Utc>) -> Vec<(TemplateID, f64)> {
+        let last_author = self.templates.values()
+            .filter(|template| template.stats.last_posted.is_some())
+            .max_by_key(|template| template.stats.last_posted)
+            .and_then(|template| template.author.as_ref());
+        self.templates.iter()
+            .filter(|(_, template)| !template.held)
+            .map(|(id, template)| {
+                let same_author = template.author.is_some() && template.author.as_ref() == last_author;
+                (*id, template.stats.weight(same_author, now))
+            })
+            .collect()
+    }
+
+    // Templates that match every word of the query, newest first. a word is
+    // looked for in the title and text, except #tag (or tag:tag, since
+    // mastodon turns a # into a link), by:account and lang:code
+    pub fn search(&self, query: &str) -> Vec<(TemplateID, &Template)> {
+        let words: Vec<String> = query.split_whitespace().map(|word| word.to_lowercase()).collect();
+        let mut found: Vec<(TemplateID, &Template)> = self.templates.iter()
+            .filter(|(_, template)| {
+                let text = template.literal_text();
+                words.iter().all(|word| if word.starts_with('#') {
+                    template.has_tag(word)
+                } else if word.starts_with("tag:") {
+                    template.has_tag(&word[4..])
+                } else if word.starts_with("by:") {
+                    template.author.as_ref()
+                        .map_or(false, |author| author.to_lowercase() == word[3..].trim_start_matches('@'))
+                } else if word.starts_with("lang:") {
+                    template.language.as_ref().map_or(false, |lang| lang.to_lowercase() == word[5..])
+                } else {
+                    text.contains(word.as_str())
+                })
+            })
+            .map(|(id, template)| (*id, template))
+            .collect();
+        found.reverse();
+        found
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&TemplateID, &Template)> {
+        self.templates.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Library, Submitted, TemplateID};
+    use chrono::{Duration, Utc};
+    use pos::POS;
+    use template::Template;
+    #[test]
+    fn ids_survive_removal() {
+        let mut library = Library::new();
+        let first = library.add(Template::parse("a [noun]").unwrap());
+        let second = library.add(Template::parse("b [verb]").unwrap());
+        library.remove(first);
+        let third = library.add(Template::parse("c [adverb]").unwrap());
+        assert_ne!(third, first);
+        assert_ne!(third, second);
+        assert!(library.get(first).is_none());
+        assert_eq!(library.get(second).unwrap().body[1].pos, Some(POS::Verb));
+        assert_eq!(library.len(), 2);
+    }
+    #[test]
+    fn submit_counts_duplicates() {
+        let mut library = Library::new();
+        let id = match library.submit(Template::parse("a [noun] b").unwrap()) {
+            Submitted::New(id) => id,
+            other => panic!("expected a new template, got {:?}", other),
+        };
+        assert_eq!(library.submit(Template::parse("A  [noun] b").unwrap()),
+                   Submitted::Duplicate(id));
+        assert_eq!(library.submit(Template::parse("a [noun], b.").unwrap()),
+                   Submitted::NearDuplicate(id));
+        assert_eq!(library.len(), 1);
+        assert_eq!(library.get(id).unwrap().submissions, 3);
+
+        let mut import = Library::new();
+        let dup = import.add(Template::parse("a [noun] b").unwrap());
+        let fresh = import.add(Template::parse("c [verb] d").unwrap());
+        let moved = library.merge(import);
+        assert_eq!(moved[&dup], id);
+        assert_ne!(moved[&fresh], id);
+        assert_eq!(library.get(id).unwrap().submissions, 4);
+        assert_eq!(library.len(), 2);
+    }
+    #[test]
+    fn held_stay_apart() {
+        let mut library = Library::new();
+        let mut held = Template::parse("my big red [noun] of badword").unwrap();
+        held.held = true;
+        let held = library.add(held);
+        let clean = library.submit(Template::parse("Title: my big red [noun] of").unwrap());
+        assert_ne!(clean.id(), held);
+        assert_eq!(library.get(held).unwrap().submissions, 1);
+        assert!(library.get(held).unwrap().title.is_none());
+        assert_eq!(library.len(), 2);
+    }
+    #[test]
+    fn weights() {
+        let mut library = Library::new();
+        let by = |author: &str, text: &str| {
+            let mut template = Template::parse(text).unwrap();
+            template.author = Some(author.to_string());
+            template
+        };
+        let first = library.add(by("alice", "a [noun]"));
+        let second = library.add(by("alice", "b [noun]"));
+        let third = library.add(by("bob", "c [noun]"));
+        let mut held = by("bob", "d [noun]");
+        held.held = true;
+        library.add(held);
+        let now = Utc::now();
+        library.mark_posted(first, now - Duration::days(30));
+        let weights = library.weights(now);
+        assert_eq!(weights.len(), 3);
+        // alice was last, so her other one is less likely than bob's
+        assert_eq!(weights[1], (second, 0.5));
+        assert_eq!(weights[2], (third, 1.0));
+        assert_eq!(library.get(first).unwrap().stats.posted, 1);
+    }
+    #[test]
+    fn search() {
+        let mut library = Library::new();
+        let mut fruit = Template::parse("Fruit salad: my [noun] ate a Banana").unwrap();
+        fruit.author = Some("alice@example.com".to_string());
+        fruit.language = Some("en".to_string());
+        fruit.add_tags(&["food".to_string()]);
+        let fruit = library.add(fruit);
+        let veg = library.add(Template::parse("a [noun] ate a carrot").unwrap());
+        let ids = |query| library.search(query).iter().map(|(id, _)| *id).collect::<Vec<TemplateID>>();
+        // newest first, and blanks aren't text
+        assert_eq!(ids("ate a"), vec![veg, fruit]);
+        assert!(ids("noun").is_empty());
+        assert_eq!(ids("SALAD banana"), vec![fruit]);
+        assert_eq!(ids("#food ate"), vec![fruit]);
+        assert_eq!(ids("tag:food"), vec![fruit]);
+        assert_eq!(ids("by:@alice@example.com"), vec![fruit]);
+        assert!(ids("lang:en carrot").is_empty());
+    }
+}
@@ -2,10 +2,55 @@
 
 extern crate madlibs;
 
-use madlibs::bot;
+use std::fs::File;
+
+use madlibs::bot::{self, BotStatus};
 
 fn main() {
     simple_logger::init_with_level(log::Level::Info).unwrap();
-    bot::run();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        // `madlibs find <words>` searches the templates like !find, without
+        // starting the bot
+        Some("find") => find(&args[1..].join(" ")),
+        // `madlibs import <status.json>` adds the templates from another
+        // bot's status file, ones we already have count as resubmissions
+        Some("import") => import(args.get(1).expect("which status file should i import?")),
+        _ => bot::run(),
+    }
+}
+
+fn find(query: &str) {
+    let bot_status = bot::get_status();
+    for (id, template) in bot_status.known_templates.search(query) {
+        let author = template.author.as_ref().map_or("someone", |author| author.as_str());
+        let created = template.created
+            .map_or("a while ago".to_string(), |created| created.format("%Y-%m-%d").to_string());
+        let mut about = format!("#{}, sent by {} on {}", id, author, created);
+        if let Some(language) = &template.language {
+            about.push_str(&format!(" in {}", language));
+        }
+        if !template.tags.is_empty() {
+            about.push_str(&format!(", tagged {}", template.tags.join(", ")));
+        }
+        if template.held {
+            about.push_str(" (held)");
+        }
+        println!("{}\n{}\n", about, template.source());
+    }
+}
+
+fn import(path: &str) {
+    let file = File::open(path).expect("couldn't open the status file to import");
+    let other: BotStatus = serde_json::from_reader(file).expect("couldn't parse the status file to import");
+    let mut bot_status = bot::get_status();
+    let before = bot_status.known_templates.len();
+    let moved = bot_status.known_templates.merge(other.known_templates);
+    for (old_id, id) in &moved {
+        println!("#{} -> #{}", old_id, id);
+    }
+    bot::save_status(&bot_status);
+    println!("imported {} templates, {} of them new", moved.len(),
+             bot_status.known_templates.len() - before);
 }
 
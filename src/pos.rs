@@ -1,5 +1,6 @@
 use senna::pos::POS as SPOS;
 use bimap::BiMap;
+use std::collections::HashMap;
 
 #[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
 // Unfortunately we have to redefine this entire enum or it errors with an
@@ -49,6 +50,38 @@ lazy_static! {
         m
     };
 }
+// Every POS with an example word, in the same order as the docs
+pub const EXAMPLES: [(POS, &str); 17] = [
+    (POS::Adjective, "slimy"),
+    (POS::Comparative, "warmer"),
+    (POS::Superlative, "coolest"),
+    (POS::Noun, "volcano"),
+    (POS::Nouns, "sheep"),
+    (POS::Proper, "John"),
+    (POS::Propers, "Alices"),
+    (POS::Pronoun, "she"),
+    (POS::Possessive, "his"),
+    (POS::Adverb, "sweetly"),
+    (POS::Uh, "uh"),
+    (POS::Verb, "eat"),
+    (POS::Verbs, "eats"),
+    (POS::Verbed, "ate"),
+    (POS::Participle, "eaten"),
+    (POS::Verbing, "eating"),
+    (POS::Question, "what"),
+];
+
+// e.g. "1x noun, 2x verbs", in doc order so it doesn't shuffle around between
+// toots
+pub fn format_requirements(requirements: &HashMap<POS, usize>) -> String {
+    EXAMPLES.iter()
+        .filter_map(|(pos, _)| requirements.get(pos).map(|count| {
+            format!("{}x {}", count, pos_to_str(pos))
+        }))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 pub fn str_to_pos(name: &str) -> Option<POS> {
     STR_TO_POS.get_by_left(&name).cloned()
 }
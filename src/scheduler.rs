@@ -0,0 +1,433 @@
+// What the bot does with its own posts, as opposed to ones people ask for.
+// This is point 6 of the TODO: if there's a game open, sometimes bump it
+// instead of posting, and if there isn't, sometimes open one. Schedule is
+// when they're allowed to go out
+
+use rand::Rng;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    // reply to the oldest open game with what it needs and boost it again
+    Bump,
+    // open a game with a template from the library
+    NewGame,
+    // fill one in from the timeline, like before there were games
+    Automatic,
+}
+
+// Goes in config.toml as a [policy] table
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Policy {
+    // with a game open, how often we bump it instead of posting
+    pub bump_chance: f64,
+    // with no game open, how often we open one instead of posting
+    pub new_game_chance: f64,
+    // hours between posts are picked between these
+    pub min_hours: i64,
+    pub max_hours: i64,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            bump_chance: 0.5,
+            new_game_chance: 0.25,
+            min_hours: 1,
+            max_hours: 24,
+        }
+    }
+}
+
+// Generic over the rng so tests can seed it and get the same answers every time
+pub struct Scheduler<R: Rng> {
+    policy: Policy,
+    rng: R,
+}
+
+impl<R: Rng> Scheduler<R> {
+    pub fn new(policy: Policy, rng: R) -> Self {
+        Self {
+            policy,
+            rng,
+        }
+    }
+
+    // open_games is how many are going now. a new game is only an option when
+    // none are, so it can't go past max_open_games unless that's 0
+    pub fn next_action(&mut self, open_games: usize, max_open_games: usize) -> Action {
+        if open_games > 0 {
+            if self.rng.gen_bool(clamp(self.policy.bump_chance)) {
+                return Action::Bump;
+            }
+        } else if max_open_games > 0 && self.rng.gen_bool(clamp(self.policy.new_game_chance)) {
+            return Action::NewGame;
+        }
+        Action::Automatic
+    }
+
+    // how long until the next one
+    pub fn next_wait(&mut self) -> Duration {
+        let min = self.policy.min_hours.max(0);
+        // gen_range panics on an empty range
+        let max = self.policy.max_hours.max(min + 1);
+        Duration::hours(self.rng.gen_range(min, max))
+    }
+}
+
+// gen_bool panics outside of 0..1, and a typo in the config shouldn't do that
+fn clamp(chance: f64) -> f64 {
+    if chance.is_nan() {
+        0.0
+    } else {
+        chance.max(0.0).min(1.0)
+    }
+}
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    BadDay(String),
+    BadTime(String),
+    BadTimeZone(String),
+    BadDate(String),
+    // no dash between the start and the end
+    ExpectedRange(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::BadDay(day) => write!(f, "{} isn't a day (mon, tue, ...)", day),
+            ParseError::BadTime(time) => write!(f, "{} isn't a time like 9 or 17:30", time),
+            ParseError::BadTimeZone(zone) => write!(f, "{} isn't a time zone like America/New_York", zone),
+            ParseError::BadDate(date) => write!(f, "{} isn't a date like 10-31", date),
+            ParseError::ExpectedRange(range) => write!(f, "{} should be a range like 9-17", range),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Some hours on some days of the week, like "mon-fri 17:00-23:00", "sat,sun
+// 10-22" or just "23-8", which is every day and wraps past midnight
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Window {
+    // monday first, like chrono
+    days: [bool; 7],
+    // minutes since midnight
+    start: u32,
+    end: u32,
+}
+
+impl Window {
+    pub fn parse(spec: &str) -> Result<Self, ParseError> {
+        let mut parts = spec.split_whitespace();
+        let (days, times) = match (parts.next(), parts.next()) {
+            (Some(days), Some(times)) => (parse_days(days)?, times),
+            (Some(times), None) => ([true; 7], times),
+            _ => return Err(ParseError::ExpectedRange(spec.to_string())),
+        };
+        let mut range = times.splitn(2, '-');
+        match (range.next(), range.next()) {
+            (Some(start), Some(end)) => Ok(Window {
+                days,
+                start: parse_time(start)?,
+                end: parse_time(end)?,
+            }),
+            _ => Err(ParseError::ExpectedRange(times.to_string())),
+        }
+    }
+
+    pub fn contains(&self, local: NaiveDateTime) -> bool {
+        let day = local.weekday().num_days_from_monday() as usize;
+        let minute = local.hour() * 60 + local.minute();
+        if self.start < self.end {
+            self.days[day] && minute >= self.start && minute < self.end
+        } else {
+            // the part after midnight belongs to the day it started on
+            let yesterday = (day + 6) % 7;
+            (self.days[day] && minute >= self.start) || (self.days[yesterday] && minute < self.end)
+        }
+    }
+}
+
+impl TryFrom<String> for Window {
+    type Error = ParseError;
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        Window::parse(&spec)
+    }
+}
+
+// "mon-fri", "sat,sun", "mon,wed-fri" or "*"
+fn parse_days(spec: &str) -> Result<[bool; 7], ParseError> {
+    if spec == "*" {
+        return Ok([true; 7]);
+    }
+    let day = |name: &str| {
+        let name = name.to_lowercase();
+        DAYS.iter().position(|day| name.starts_with(day))
+            .ok_or_else(|| ParseError::BadDay(name.to_string()))
+    };
+    let mut days = [false; 7];
+    for part in spec.split(',') {
+        let mut range = part.splitn(2, '-');
+        let first = day(range.next().unwrap())?;
+        let last = match range.next() {
+            Some(last) => day(last)?,
+            None => first,
+        };
+        // fri-mon goes around the weekend
+        let mut i = first;
+        days[i] = true;
+        while i != last {
+            i = (i + 1) % 7;
+            days[i] = true;
+        }
+    }
+    Ok(days)
+}
+
+// "9", "09:30" or "24" for the end of the day, as minutes since midnight
+fn parse_time(spec: &str) -> Result<u32, ParseError> {
+    let bad = || ParseError::BadTime(spec.to_string());
+    let mut parts = spec.splitn(2, ':');
+    let hours: u32 = parts.next().unwrap().parse().map_err(|_| bad())?;
+    let minutes: u32 = match parts.next() {
+        Some(minutes) => minutes.parse().map_err(|_| bad())?,
+        None => 0,
+    };
+    let time = hours * 60 + minutes;
+    if minutes >= 60 || time > MINUTES_PER_DAY {
+        return Err(bad());
+    }
+    Ok(time)
+}
+
+// A time zone from the tz database like "America/New_York", so the schedule
+// moves with daylight savings
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct TimeZone(Tz);
+
+impl TimeZone {
+    pub fn parse(spec: &str) -> Result<Self, ParseError> {
+        spec.parse()
+            .map(TimeZone)
+            .map_err(|_| ParseError::BadTimeZone(spec.to_string()))
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        TimeZone(Tz::UTC)
+    }
+}
+
+impl TryFrom<String> for TimeZone {
+    type Error = ParseError;
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        TimeZone::parse(&spec)
+    }
+}
+
+// Goes in config.toml as a [schedule] table
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Schedule {
+    // what the times in windows and quiet are in, UTC if it's not set
+    pub timezone: TimeZone,
+    // when posts can go out, any time if there aren't any
+    pub windows: Vec<Window>,
+    // when they can't, even inside a window
+    pub quiet: Vec<Window>,
+}
+
+impl Schedule {
+    // what the clock says in timezone
+    pub fn local(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        time.with_timezone(&self.timezone.0).naive_local()
+    }
+
+    pub fn allows(&self, time: DateTime<Utc>) -> bool {
+        let local = self.local(time);
+        (self.windows.is_empty() || self.windows.iter().any(|window| window.contains(local)))
+            && !self.quiet.iter().any(|window| window.contains(local))
+    }
+
+    // the first minute from time on that allows a post. a schedule that
+    // never does is ignored rather than never posting again
+    pub fn next_allowed(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        if self.allows(time) {
+            return time;
+        }
+        let minute = time - Duration::seconds(time.second() as i64)
+            - Duration::nanoseconds(time.nanosecond() as i64);
+        // windows repeat every week, so looking further won't find anything
+        (1..=7 * MINUTES_PER_DAY as i64)
+            .map(|i| minute + Duration::minutes(i))
+            .find(|time| self.allows(*time))
+            .unwrap_or_else(|| {
+                warn!("the schedule never allows a post, ignoring it");
+                time
+            })
+    }
+}
+
+// A month and a day, any year, like "10-31"
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String")]
+pub struct MonthDay(u32, u32);
+
+impl MonthDay {
+    pub fn parse(spec: &str) -> Result<Self, ParseError> {
+        let bad = || ParseError::BadDate(spec.to_string());
+        let mut parts = spec.splitn(2, '-');
+        let month = parts.next().unwrap().parse().map_err(|_| bad())?;
+        let day = parts.next().ok_or_else(bad)?.parse().map_err(|_| bad())?;
+        // 2020 so the 29th of february is fine
+        NaiveDate::from_ymd_opt(2020, month, day).ok_or_else(bad)?;
+        Ok(MonthDay(month, day))
+    }
+
+    fn of(date: NaiveDate) -> Self {
+        MonthDay(date.month(), date.day())
+    }
+}
+
+impl TryFrom<String> for MonthDay {
+    type Error = ParseError;
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        MonthDay::parse(&spec)
+    }
+}
+
+// Templates with a tag that get played instead of the rest for part of the
+// year, one [[pools]] table each in config.toml
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Pool {
+    pub tag: String,
+    // both days count, and until can be before from to go over new year's
+    pub from: MonthDay,
+    pub until: MonthDay,
+}
+
+impl Pool {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let day = MonthDay::of(date);
+        if self.from <= self.until {
+            day >= self.from && day <= self.until
+        } else {
+            day >= self.from || day <= self.until
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Scheduler, Policy, Action, Schedule, Window, TimeZone, ParseError, Pool, MonthDay};
+    use chrono::NaiveDate;
+    use chrono::{DateTime, Utc};
+    use rand::{SeedableRng, rngs::StdRng};
+    fn actions(policy: Policy, seed: u8, open_games: usize) -> Vec<Action> {
+        let mut scheduler = Scheduler::new(policy, StdRng::from_seed([seed; 32]));
+        (0..50).map(|_| scheduler.next_action(open_games, 5)).collect()
+    }
+    #[test]
+    fn policy() {
+        let policy = Policy::default();
+        // the same seed gets the same posts
+        assert_eq!(actions(policy, 1, 0), actions(policy, 1, 0));
+        // with a game open we never start another, without one we never bump
+        let open = actions(policy, 2, 1);
+        assert!(open.contains(&Action::Bump) && open.contains(&Action::Automatic));
+        assert!(!open.contains(&Action::NewGame));
+        let none = actions(policy, 2, 0);
+        assert!(none.contains(&Action::NewGame) && none.contains(&Action::Automatic));
+        assert!(!none.contains(&Action::Bump));
+        // chances of 0 and 1 (or nonsense) are always and never
+        let always = Policy { bump_chance: 1.0, new_game_chance: 7.0, ..policy };
+        assert!(actions(always, 3, 1).iter().all(|action| *action == Action::Bump));
+        assert!(actions(always, 3, 0).iter().all(|action| *action == Action::NewGame));
+        let never = Policy { bump_chance: 0.0, new_game_chance: -1.0, ..policy };
+        assert!(actions(never, 3, 1).iter().all(|action| *action == Action::Automatic));
+        assert!(actions(never, 3, 0).iter().all(|action| *action == Action::Automatic));
+        let mut no_games = Scheduler::new(always, StdRng::from_seed([4; 32]));
+        assert_eq!(no_games.next_action(0, 0), Action::Automatic);
+    }
+    #[test]
+    fn waits() {
+        let policy = Policy { min_hours: 3, max_hours: 5, ..Policy::default() };
+        let mut scheduler = Scheduler::new(policy, StdRng::from_seed([5; 32]));
+        for _ in 0..50 {
+            let hours = scheduler.next_wait().num_hours();
+            assert!(hours >= 3 && hours < 5);
+        }
+        let backwards = Policy { min_hours: 5, max_hours: 2, ..Policy::default() };
+        let mut scheduler = Scheduler::new(backwards, StdRng::from_seed([5; 32]));
+        assert_eq!(scheduler.next_wait().num_hours(), 5);
+    }
+    fn utc(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+    #[test]
+    fn windows() {
+        // 2020-01-06 was a monday
+        let weekdays = Window::parse("mon-fri 9-17:30").unwrap();
+        assert!(weekdays.contains(utc("2020-01-06T09:00:00Z").naive_utc()));
+        assert!(weekdays.contains(utc("2020-01-10T17:29:00Z").naive_utc()));
+        assert!(!weekdays.contains(utc("2020-01-10T17:30:00Z").naive_utc()));
+        assert!(!weekdays.contains(utc("2020-01-11T12:00:00Z").naive_utc()));
+        // sunday night into monday morning, but not saturday into sunday
+        let nights = Window::parse("sun 23-8").unwrap();
+        assert!(nights.contains(utc("2020-01-06T07:00:00Z").naive_utc()));
+        assert!(!nights.contains(utc("2020-01-05T07:00:00Z").naive_utc()));
+        assert_eq!(Window::parse("fri-mon 0-24").unwrap(), Window::parse("sat,sun,Monday,fri 0:00-24:00").unwrap());
+        assert_eq!(Window::parse("someday 9-5"), Err(ParseError::BadDay("someday".to_string())));
+        assert_eq!(Window::parse("9:75-10"), Err(ParseError::BadTime("9:75".to_string())));
+        assert_eq!(Window::parse("9"), Err(ParseError::ExpectedRange("9".to_string())));
+    }
+    #[test]
+    fn schedule() {
+        let schedule: Schedule = ::toml::from_str(r#"
+            timezone = "America/New_York"
+            windows = ["9-22"]
+            quiet = ["sun 0-24"]
+        "#).unwrap();
+        assert_eq!(schedule.timezone, TimeZone::parse("America/New_York").unwrap());
+        // 4am there waits until 9
+        assert_eq!(schedule.next_allowed(utc("2020-01-06T09:00:00Z")), utc("2020-01-06T14:00:00Z"));
+        // which is an hour earlier in UTC once it's summer time
+        assert_eq!(schedule.next_allowed(utc("2020-07-06T08:00:00Z")), utc("2020-07-06T13:00:00Z"));
+        // past 10pm saturday it's quiet all sunday
+        assert_eq!(schedule.next_allowed(utc("2020-01-05T03:30:20Z")), utc("2020-01-06T14:00:00Z"));
+        let now = utc("2020-01-06T15:12:34Z");
+        assert_eq!(schedule.next_allowed(now), now);
+        let never: Schedule = ::toml::from_str(r#"quiet = ["0-24"]"#).unwrap();
+        assert_eq!(never.next_allowed(now), now);
+        assert!(::toml::from_str::<Schedule>(r#"windows = ["whenever"]"#).is_err());
+        assert_eq!(TimeZone::parse("-05:00"), Err(ParseError::BadTimeZone("-05:00".to_string())));
+    }
+    #[test]
+    fn pools() {
+        let pool: Pool = ::toml::from_str("tag = \"winter\"\nfrom = \"12-20\"\nuntil = \"1-6\"").unwrap();
+        assert!(pool.contains(NaiveDate::from_ymd(2019, 12, 31)));
+        assert!(pool.contains(NaiveDate::from_ymd(2020, 1, 6)));
+        assert!(!pool.contains(NaiveDate::from_ymd(2020, 1, 7)));
+        let halloween = Pool {
+            tag: "halloween".to_string(),
+            from: MonthDay::parse("10-01").unwrap(),
+            until: MonthDay::parse("10-31").unwrap(),
+        };
+        assert!(halloween.contains(NaiveDate::from_ymd(2020, 10, 31)));
+        assert!(!halloween.contains(NaiveDate::from_ymd(2020, 12, 31)));
+        assert_eq!(MonthDay::parse("2-30"), Err(ParseError::BadDate("2-30".to_string())));
+        assert!(MonthDay::parse("2-29").is_ok());
+    }
+}
@@ -0,0 +1,391 @@
+// How each template has done when we posted it, which is what picking a
+// random one goes by. Every finished madlib we post gets a Post, and the bot
+// looks them up again for a while to see how they're doing
+
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+use bot::StatusID;
+use library::{Library, TemplateID};
+use AccountID;
+
+// posts newer than this count against a template getting picked again
+const RECENT_HOURS: i64 = 72;
+// what a template posted just now is worth next to one that wasn't
+const RECENT_FLOOR: f64 = 0.05;
+// and one by whoever sent the last template we posted
+const SAME_AUTHOR: f64 = 0.5;
+// a boost is worth this many favourites
+const BOOST_WORTH: f64 = 2.0;
+// how often we look at a post again, and for how long
+const CHECK_HOURS: i64 = 6;
+const TRACK_DAYS: i64 = 7;
+// how many of each list the report has
+const REPORT_SHOWN: usize = 5;
+// and how many madlibs a roundup has, each of them gets boosted
+const ROUNDUP_SHOWN: usize = 3;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Stats {
+    // every time it went out, automatic or as a game
+    pub posted: u64,
+    pub last_posted: Option<DateTime<Utc>>,
+    // totals over everything we posted of it
+    pub favourites: u64,
+    pub boosts: u64,
+    pub replies: u64,
+}
+
+impl Stats {
+    pub fn mark_posted(&mut self, now: DateTime<Utc>) {
+        self.posted += 1;
+        self.last_posted = Some(now);
+    }
+
+    // a post of it went from before to after
+    pub fn count(&mut self, before: &Engagement, after: &Engagement) {
+        // favourites can be taken back, so it can go down
+        self.favourites = (self.favourites + after.favourites).saturating_sub(before.favourites);
+        self.boosts = (self.boosts + after.boosts).saturating_sub(before.boosts);
+        self.replies = (self.replies + after.replies).saturating_sub(before.replies);
+    }
+
+    // favourites (and boosts) per time we posted it
+    pub fn engagement(&self) -> f64 {
+        if self.posted == 0 {
+            return 0.0;
+        }
+        (self.favourites as f64 + BOOST_WORTH * self.boosts as f64) / self.posted as f64
+    }
+
+    // how likely it is to be picked next to the others. something nobody
+    // has seen yet starts at 1, hits go up from there
+    pub fn weight(&self, same_author: bool, now: DateTime<Utc>) -> f64 {
+        let mut weight = 1.0 + self.engagement();
+        if let Some(last) = self.last_posted {
+            let since = (now - last).num_minutes() as f64;
+            let recent = Duration::hours(RECENT_HOURS).num_minutes() as f64;
+            if since < recent {
+                weight *= (since / recent).max(RECENT_FLOOR);
+            }
+        }
+        if same_author {
+            weight *= SAME_AUTHOR;
+        }
+        weight
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Engagement {
+    pub favourites: u64,
+    pub boosts: u64,
+    pub replies: u64,
+}
+
+impl Engagement {
+    pub fn score(&self) -> f64 {
+        self.favourites as f64 + BOOST_WORTH * self.boosts as f64
+    }
+}
+
+// One finished madlib we posted, under the ID of its first toot
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Post {
+    pub template_id: TemplateID,
+    // the game it finished, None for automatic ones
+    #[serde(default)]
+    pub game: Option<StatusID>,
+    // everyone whose words made it in
+    #[serde(default)]
+    pub contributors: Vec<AccountID>,
+    pub posted: DateTime<Utc>,
+    #[serde(default)]
+    pub checked: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub engagement: Engagement,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+impl Post {
+    pub fn new(template_id: TemplateID, game: Option<StatusID>, contributors: Vec<AccountID>, now: DateTime<Utc>) -> Self {
+        Self {
+            template_id,
+            game,
+            contributors,
+            posted: now,
+            checked: None,
+            engagement: Engagement::default(),
+            deleted: false,
+        }
+    }
+
+    // new enough that it's still getting favourites, and not looked at lately
+    pub fn needs_check(&self, now: DateTime<Utc>) -> bool {
+        !self.deleted && now - self.posted < Duration::days(TRACK_DAYS)
+            && self.checked.map_or(true, |checked| now - checked >= Duration::hours(CHECK_HOURS))
+    }
+}
+
+// What one player's words got in the posts we've stopped keeping
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PlayerTotals {
+    pub games: usize,
+    pub favourites: u64,
+}
+
+// Folds the posts nothing looks at anymore into their players' totals, so
+// the status file doesn't keep every one of them forever. anything since the
+// last roundup is kept for the next one
+pub fn prune(posts: &mut HashMap<StatusID, Post>, totals: &mut HashMap<AccountID, PlayerTotals>,
+             last_roundup: Option<DateTime<Utc>>, now: DateTime<Utc>) {
+    let mut cutoff = now - Duration::days(TRACK_DAYS);
+    if let Some(last) = last_roundup {
+        cutoff = cutoff.min(last);
+    }
+    let old: Vec<StatusID> = posts.iter()
+        .filter(|(_, post)| post.posted < cutoff)
+        .map(|(toot_id, _)| toot_id.clone())
+        .collect();
+    for toot_id in old {
+        let post = posts.remove(&toot_id).unwrap();
+        for acct in post.contributors {
+            let total = totals.entry(acct).or_default();
+            total.games += 1;
+            total.favourites += post.engagement.favourites;
+        }
+    }
+}
+
+// Who sent us the word for what in these posts (and the ones already folded
+// into totals), leaving out anyone who asked not to be on the leaderboard
+fn tally_contributors<'a, I>(posts: I, totals: &'a HashMap<AccountID, PlayerTotals>, hidden: &HashSet<AccountID>) -> Vec<(&'a AccountID, usize, u64)>
+        where I: IntoIterator<Item = &'a Post> {
+    let mut tally: HashMap<&AccountID, (usize, u64)> = totals.iter()
+        .filter(|(acct, _)| !hidden.contains(*acct))
+        .map(|(acct, total)| (acct, (total.games, total.favourites)))
+        .collect();
+    for post in posts {
+        for acct in post.contributors.iter().filter(|acct| !hidden.contains(*acct)) {
+            let entry = tally.entry(acct).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += post.engagement.favourites;
+        }
+    }
+    let mut tally: Vec<_> = tally.into_iter()
+        .map(|(acct, (games, favourites))| (acct, games, favourites))
+        .collect();
+    // most favourites first, then most games, then by name so it's stable
+    tally.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.cmp(&a.1)).then(a.0.cmp(b.0)));
+    tally
+}
+
+// The templates that do best and worst once we post them, and the people
+// whose words end up in the most liked games
+pub fn report(library: &Library, posts: &HashMap<StatusID, Post>, totals: &HashMap<AccountID, PlayerTotals>, hidden: &HashSet<AccountID>) -> String {
+    let mut posted: Vec<(&TemplateID, f64, u64)> = library.iter()
+        .filter(|(_, template)| template.stats.posted > 0)
+        .map(|(id, template)| (id, template.stats.engagement(), template.stats.posted))
+        .collect();
+    if posted.is_empty() {
+        return "i haven't posted anything to keep track of yet".to_string();
+    }
+    posted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(b.0)));
+    let line = |(id, engagement, times): &(&TemplateID, f64, u64)| {
+        format!("#{}: {:.1} per post ({} posts)", id, engagement, times)
+    };
+    let mut text = String::from("best templates:\n");
+    for template in posted.iter().take(REPORT_SHOWN) {
+        text.push_str(&format!("\n{}", line(template)));
+    }
+    // only ones that have had a few chances count as duds
+    let duds: Vec<String> = posted.iter().rev()
+        .filter(|(_, _, times)| *times >= 3)
+        .take(REPORT_SHOWN)
+        .map(line)
+        .collect();
+    if !duds.is_empty() {
+        text.push_str(&format!("\n\nworst templates:\n\n{}", duds.join("\n")));
+    }
+    let contributors = tally_contributors(posts.values(), totals, hidden);
+    if !contributors.is_empty() {
+        text.push_str("\n\nmost liked players:\n");
+        for (acct, games, favourites) in contributors.iter().take(REPORT_SHOWN) {
+            text.push_str(&format!("\n{}: {} favourites over {} games", acct, favourites, games));
+        }
+    }
+    text
+}
+
+// The best madlibs posted since then and who played the most liked games,
+// for the weekly (or monthly) post. Also returns the madlibs, to boost them
+pub fn roundup(library: &Library, posts: &HashMap<StatusID, Post>, since: DateTime<Utc>, period: &str, hidden: &HashSet<AccountID>) -> Option<(String, Vec<StatusID>)> {
+    let mut recent: Vec<(&StatusID, &Post)> = posts.iter()
+        .filter(|(_, post)| post.posted >= since && !post.deleted)
+        .collect();
+    if recent.is_empty() {
+        return None;
+    }
+    recent.sort_by(|a, b| b.1.engagement.score().partial_cmp(&a.1.engagement.score()).unwrap()
+                   .then(a.1.posted.cmp(&b.1.posted)));
+    let mut text = format!("the best madlibs this {}:\n", period);
+    for (i, (_, post)) in recent.iter().take(ROUNDUP_SHOWN).enumerate() {
+        let title = library.get(post.template_id)
+            .and_then(|template| template.title.clone())
+            .unwrap_or_else(|| "Untitled".to_string());
+        text.push_str(&format!("\n{}. **{}** (#{}), {} favourites and {} boosts",
+                               i + 1, title, post.template_id,
+                               post.engagement.favourites, post.engagement.boosts));
+    }
+    // only this period's games count, not everyone's totals
+    let none = HashMap::new();
+    let contributors = tally_contributors(recent.iter().map(|(_, post)| *post), &none, hidden);
+    if !contributors.is_empty() {
+        text.push_str(&format!("\n\nthis {}'s top players:\n", period));
+        for (i, (acct, games, favourites)) in contributors.iter().take(REPORT_SHOWN).enumerate() {
+            text.push_str(&format!("\n{}. {}, {} favourites over {} games", i + 1, acct, favourites, games));
+        }
+        text.push_str("\n\n(!leaderboard off keeps you off this list)");
+    }
+    let best = recent.iter().take(ROUNDUP_SHOWN).map(|(toot_id, _)| (*toot_id).clone()).collect();
+    Some((text, best))
+}
+
+// Picks one of the weights' keys, each as likely as its weight says
+pub fn choose_weighted<T: Copy, R: Rng>(weights: &[(T, f64)], rng: &mut R) -> Option<T> {
+    let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+    if total.is_nan() || total <= 0.0 {
+        return None;
+    }
+    let mut left = rng.gen_range(0.0, total);
+    for (item, weight) in weights {
+        if left < *weight {
+            return Some(*item);
+        }
+        left -= weight;
+    }
+    // rounding can leave a sliver at the end
+    weights.last().map(|(item, _)| *item)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Stats, Engagement, Post, PlayerTotals, choose_weighted, tally_contributors, roundup, prune};
+    use chrono::{Duration, Utc};
+    use std::collections::{HashMap, HashSet};
+    use library::Library;
+    use template::Template;
+    use rand::{SeedableRng, rngs::StdRng};
+    #[test]
+    fn weights() {
+        let now = Utc::now();
+        let fresh = Stats::default();
+        assert_eq!(fresh.weight(false, now), 1.0);
+        assert_eq!(fresh.weight(true, now), 0.5);
+        let hit = Stats { posted: 2, last_posted: Some(now - Duration::days(30)), favourites: 6, boosts: 2, replies: 0 };
+        assert_eq!(hit.weight(false, now), 6.0);
+        // the same hit posted a moment ago is hardly ever picked
+        let mut again = hit.clone();
+        again.mark_posted(now);
+        assert_eq!(again.posted, 3);
+        assert!(again.weight(false, now) < fresh.weight(false, now) / 2.0);
+        let yesterday = Stats { last_posted: Some(now - Duration::hours(24)), ..hit.clone() };
+        assert!(yesterday.weight(false, now) < hit.weight(false, now));
+        assert!(yesterday.weight(false, now) > again.weight(false, now));
+    }
+    #[test]
+    fn choosing() {
+        let mut rng = StdRng::from_seed([1; 32]);
+        assert_eq!(choose_weighted::<u64, _>(&[], &mut rng), None);
+        assert_eq!(choose_weighted(&[(1, 0.0)], &mut rng), None);
+        assert_eq!(choose_weighted(&[(1, 0.0), (2, 3.0)], &mut rng), Some(2));
+        let picks: Vec<u64> = (0..1000)
+            .filter_map(|_| choose_weighted(&[(1, 1.0), (2, 9.0)], &mut rng))
+            .collect();
+        let ones = picks.iter().filter(|id| **id == 1).count();
+        assert!(ones > 50 && ones < 150);
+    }
+    #[test]
+    fn engagement() {
+        let now = Utc::now();
+        let mut post = Post::new(0, None, vec!["alice".to_string()], now - Duration::hours(1));
+        assert!(post.needs_check(now));
+        post.checked = Some(now);
+        assert!(!post.needs_check(now + Duration::hours(1)));
+        assert!(post.needs_check(now + Duration::hours(6)));
+        assert!(!post.needs_check(now + Duration::days(7)));
+        let mut stats = Stats { posted: 2, favourites: 3, ..Stats::default() };
+        let after = Engagement { favourites: 2, boosts: 1, replies: 1 };
+        stats.count(&post.engagement, &after);
+        assert_eq!((stats.favourites, stats.boosts, stats.replies), (5, 1, 1));
+        // someone took a favourite back
+        stats.count(&after, &Engagement { favourites: 1, ..after });
+        assert_eq!(stats.favourites, 4);
+        post.engagement = after;
+        let mut posts = HashMap::new();
+        posts.insert("1".to_string(), post.clone());
+        let bob = Post { contributors: vec!["alice".to_string(), "bob".to_string()], ..post };
+        posts.insert("2".to_string(), Post { engagement: Engagement::default(), ..bob });
+        let none = HashMap::new();
+        let tally = tally_contributors(posts.values(), &none, &HashSet::new());
+        assert_eq!(tally[0], (&"alice".to_string(), 2, 2));
+        assert_eq!(tally[1], (&"bob".to_string(), 1, 0));
+        let mut hidden = HashSet::new();
+        hidden.insert("alice".to_string());
+        assert_eq!(tally_contributors(posts.values(), &none, &hidden), vec![(&"bob".to_string(), 1, 0)]);
+    }
+    #[test]
+    fn pruning() {
+        let now = Utc::now();
+        let alice = vec!["alice".to_string()];
+        let post = |favourites, days| Post {
+            engagement: Engagement { favourites, ..Engagement::default() },
+            ..Post::new(0, None, alice.clone(), now - Duration::days(days))
+        };
+        let mut posts = HashMap::new();
+        posts.insert("old".to_string(), post(3, 20));
+        posts.insert("older".to_string(), post(2, 30));
+        posts.insert("new".to_string(), post(1, 2));
+        let mut totals = HashMap::new();
+        // the roundup hasn't gone out in a while, so it still wants "old"
+        prune(&mut posts, &mut totals, Some(now - Duration::days(25)), now);
+        assert_eq!(posts.len(), 2);
+        assert_eq!(totals["alice"], PlayerTotals { games: 1, favourites: 2 });
+        prune(&mut posts, &mut totals, None, now);
+        assert_eq!(posts.keys().collect::<Vec<_>>(), vec!["new"]);
+        assert_eq!(totals["alice"], PlayerTotals { games: 2, favourites: 5 });
+        // the report still counts them all
+        let tally = tally_contributors(posts.values(), &totals, &HashSet::new());
+        assert_eq!(tally, vec![(&"alice".to_string(), 3, 6)]);
+    }
+    #[test]
+    fn roundups() {
+        let now = Utc::now();
+        let mut library = Library::new();
+        let id = library.add(Template::parse("fruit: a [noun]").unwrap());
+        let mut posts = HashMap::new();
+        let alice = vec!["alice".to_string()];
+        let post = |engagement, days| Post {
+            engagement,
+            ..Post::new(id, None, alice.clone(), now - Duration::days(days))
+        };
+        posts.insert("old".to_string(), post(Engagement { favourites: 99, ..Engagement::default() }, 10));
+        posts.insert("meh".to_string(), post(Engagement { favourites: 1, ..Engagement::default() }, 2));
+        posts.insert("hit".to_string(), post(Engagement { favourites: 1, boosts: 2, replies: 0 }, 3));
+        let since = now - Duration::days(7);
+        let (text, best) = roundup(&library, &posts, since, "week", &HashSet::new()).unwrap();
+        assert_eq!(best, vec!["hit".to_string(), "meh".to_string()]);
+        assert!(text.starts_with("the best madlibs this week:\n\n1. **fruit** (#0), 1 favourites and 2 boosts"));
+        assert!(text.contains("1. alice, 2 favourites over 2 games"));
+        let mut hidden = HashSet::new();
+        hidden.insert("alice".to_string());
+        let (text, _) = roundup(&library, &posts, since, "week", &hidden).unwrap();
+        assert!(!text.contains("alice"));
+        assert!(roundup(&library, &posts, now, "week", &hidden).is_none());
+    }
+}
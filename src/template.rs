@@ -7,16 +7,24 @@ use regex::Regex;
 use rand::Rng;
 use std::collections::HashSet;
 use std::collections::HashMap;
+use chrono::{DateTime, Utc};
 
 use pos::*;
 
 use sanitize_all;
+use filter::Filter;
+use collection::Limits;
+use stats::Stats;
+use AccountID;
 
 #[derive(Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct Token {
     pub text: Option<String>,
     pub is_placeholder: bool,
     pub pos: Option<POS>,
+    // what the template author had in mind for this blank, `[noun: a fruit]`
+    #[serde(default)]
+    pub hint: Option<String>,
 }
 
 impl Token {
@@ -25,6 +33,7 @@ impl Token {
             text: Some(text),
             is_placeholder: false,
             pos: None,
+            hint: None,
         }
     }
     pub fn new_str(text: &str) -> Self {
@@ -35,6 +44,7 @@ impl Token {
             text: None,
             is_placeholder: true,
             pos: Some(pos),
+            hint: None,
         }
     }
 }
@@ -57,8 +67,117 @@ impl std::fmt::Debug for Token {
 pub struct Template {
     pub title: Option<String>,
     pub body: Vec<Token>,
+    // how many times people sent us this (or something close enough)
+    #[serde(default = "one")]
+    pub submissions: u32,
+    // whoever first sent it, None for ones from before we kept track
+    #[serde(default)]
+    pub author: Option<AccountID>,
+    // tripped the filter, so nothing plays it until an admin approves it
+    #[serde(default)]
+    pub held: bool,
+    // what to do with manual responses the tagger thinks are the wrong POS
+    #[serde(default)]
+    pub strictness: Strictness,
+    // None goes by the title, like it always has
+    #[serde(default)]
+    pub mode: Option<Mode>,
+    // rules for its games, None for whatever the config says
+    #[serde(default)]
+    pub limits: Option<Limits>,
+    // how it did when we posted it
+    #[serde(default)]
+    pub stats: Stats,
+    // like halloween or poetry, see normalize_tag
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // when it was first sent, None for ones from before we kept track
+    #[serde(default)]
+    pub created: Option<DateTime<Utc>>,
+    // what the instance said the toot was in, like "en"
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
+// What happens when someone sends us a template
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    // a game, people fill it in
+    Manual,
+    // we fill it in from the timeline right away
+    Automatic,
+    // a game, but a short one, and the timeline fills in whatever's left
+    Hybrid,
+}
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "manual" => Some(Mode::Manual),
+            "automatic" | "auto" => Some(Mode::Automatic),
+            "hybrid" => Some(Mode::Hybrid),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Manual => "manual",
+            Mode::Automatic => "automatic",
+            Mode::Hybrid => "hybrid",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    // take whatever people say it is
+    Off,
+    // take it, but tell them
+    Warn,
+    // don't take it
+    Reject,
+}
+impl Default for Strictness {
+    fn default() -> Self {
+        Strictness::Off
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+// What's in the () at the start of a template, like `(hybrid, one word each)`:
+// a mode, rules for its games, or both. None if it's something else
+fn parse_header(header: &str) -> Option<(Option<Mode>, Option<Limits>)> {
+    let mut mode = None;
+    let mut limits = None;
+    for part in header.split(',') {
+        if let Some(named) = Mode::from_name(&part.trim().to_lowercase()) {
+            mode = Some(named);
+        } else if !limits.get_or_insert_with(Limits::default).add_rule(part) {
+            return None;
+        }
+    }
+    Some((mode, limits))
+}
+
+// Lowercase and without the #, so #Halloween and halloween are the same tag.
+// None if there's nothing left
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag: String = tag.trim_start_matches('#')
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_')
+        .collect::<String>()
+        .to_lowercase();
+    Some(tag).filter(|tag| !tag.is_empty())
+}
+
+// How much literal text two templates with the same blanks need to share
+// before we call them the same template
+const NEAR_DUPLICATE_SIMILARITY: f64 = 0.8;
+
 fn sanitize_source(status: &str) -> String {
     let status = sanitize_all(status);
     // Remove URLs that make https [plural] and // [noun]
@@ -98,6 +217,7 @@ fn label_status(status: &str) -> Vec<Token> {
                 is_placeholder: true,
                 // CHECK: None here might cause disaster
                 pos: senna_to_pos(sen_pos),
+                hint: None,
             };
             labelled.push(token);
         }
@@ -105,8 +225,40 @@ fn label_status(status: &str) -> Vec<Token> {
     labelled
 }
 
+// What the tagger makes of a word someone sent in, with no context around it
+// For more than one word it goes by the last one, which is usually the head
+pub fn tag_word(word: &str) -> Option<POS> {
+    let word = sanitize_source(word);
+    let mut senna = Senna::new("rust-senna/senna/".to_string());
+    let options = SennaParseOptions {
+        psg: false,
+        pos: true,
+    };
+    let sen = senna.parse(&word, options);
+    sen.get_words().last().and_then(|word| senna_to_pos(word.get_pos()))
+}
+
 impl Template {
 
+    // Nothing but the words, everything else is what a template we've never
+    // seen before starts out with
+    pub fn new(title: Option<String>, body: Vec<Token>) -> Self {
+        Template {
+            title,
+            body,
+            submissions: 1,
+            author: None,
+            held: false,
+            strictness: Strictness::default(),
+            mode: None,
+            limits: None,
+            stats: Stats::default(),
+            tags: Vec::new(),
+            created: None,
+            language: None,
+        }
+    }
+
     fn collect(&self) -> String {
         let body = self.body.iter().fold(String::new(), |s, token| {
             format!("{}{}", s, token.text.as_ref().unwrap())
@@ -141,11 +293,40 @@ impl Template {
         return false;
     }
 
+    // Fills in the nth blank (from 0) no matter what's before it, returns
+    // false if there's no such blank or it's already filled
+    pub fn fill_blank(&mut self, n: usize, word: String) -> bool {
+        match self.body.iter_mut().filter(|token| token.pos.is_some()).nth(n) {
+            Some(token) if token.is_placeholder => {
+                token.text = Some(word);
+                token.is_placeholder = false;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // The number fill_blank would want for the first empty blank for pos
+    pub fn free_blank(&self, pos: POS) -> Option<usize> {
+        self.body.iter()
+            .filter(|token| token.pos.is_some())
+            .position(|token| token.is_placeholder && token.pos == Some(pos))
+    }
+
+    // Every blank, filled in or not, in order, so that filling one in doesn't
+    // change what number the others are
+    pub fn blanks(&self) -> Vec<&Token> {
+        self.body.iter()
+            .filter(|token| token.pos.is_some())
+            .collect()
+    }
+
     // Modifies self in-line
     // Returns self.check_done()
     // Only fills in one word, exits immediately
     // (i.e. it's made for one word per status)
-    pub fn reduce(&mut self, status: &str) -> Option<String> {
+    // Never picks a word the filter doesn't allow
+    pub fn reduce(&mut self, status: &str, filter: &Filter) -> Option<String> {
         let mut status = label_status(&sanitize_source(&status));
         // Don't just take the first one, because that tends to be boring
         let mut rng = rand::thread_rng();
@@ -153,7 +334,7 @@ impl Template {
         for loan_word in status {
             // this is actually a complicated `if` lacking good `let` combinations
             match (loan_word.pos, loan_word.text) {
-                (Some(pos), Some(text)) if loan_word.is_placeholder => if self.insert_placeholder(pos, text) {
+                (Some(pos), Some(text)) if loan_word.is_placeholder && filter.allows_word(&text) => if self.insert_placeholder(pos, text) {
                     break;
                 }
                 _ => (),
@@ -162,9 +343,161 @@ impl Template {
         self.check_done()
     }
 
+    pub fn mode(&self) -> Mode {
+        match (self.mode, &self.title) {
+            (Some(mode), _) => mode,
+            (None, Some(_)) => Mode::Manual,
+            (None, None) => Mode::Automatic,
+        }
+    }
+
+    // Lowercase, whitespace squashed, blanks as [pos], so templates that only
+    // differ in formatting (or hints) come out equal
+    pub fn normalized(&self) -> String {
+        let mut text = String::new();
+        if let Some(title) = &self.title {
+            text.push_str(title);
+            text.push_str(": ");
+        }
+        for token in &self.body {
+            if token.is_placeholder {
+                text.push_str(&format!(" [{}] ", pos_to_str(&token.pos.unwrap())));
+            } else if let Some(literal) = &token.text {
+                text.push_str(literal);
+            }
+        }
+        text.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    pub fn pos_sequence(&self) -> Vec<POS> {
+        self.body.iter()
+            .filter(|token| token.is_placeholder)
+            .filter_map(|token| token.pos)
+            .collect()
+    }
+
+    fn literal_words(&self) -> HashSet<String> {
+        let mut words = HashSet::new();
+        for token in &self.body {
+            if token.is_placeholder {
+                continue;
+            }
+            if let Some(literal) = &token.text {
+                for word in literal.split_whitespace() {
+                    let word: String = word.chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .flat_map(|c| c.to_lowercase())
+                        .collect();
+                    if word != "" {
+                        words.insert(word);
+                    }
+                }
+            }
+        }
+        words
+    }
+
+    // Same blanks in the same order, and (nearly) the same words around them,
+    // ignoring punctuation, case and the title
+    pub fn is_near_duplicate(&self, other: &Template) -> bool {
+        if self.pos_sequence() != other.pos_sequence() {
+            return false;
+        }
+        let ours = self.literal_words();
+        let theirs = other.literal_words();
+        let union = ours.union(&theirs).count();
+        // two templates that are nothing but blanks
+        if union == 0 {
+            return true;
+        }
+        let shared = ours.intersection(&theirs).count();
+        shared as f64 / union as f64 >= NEAR_DUPLICATE_SIMILARITY
+    }
+
+    // Back in the [bracket] language, as someone would have typed it
+    pub fn source(&self) -> String {
+        let body = self.body.iter().fold(String::new(), |s, token| {
+            if token.is_placeholder {
+                match &token.hint {
+                    Some(hint) => format!("{}[{}: {}]", s, pos_to_str(&token.pos.unwrap()), hint),
+                    None => format!("{}[{}]", s, pos_to_str(&token.pos.unwrap())),
+                }
+            } else {
+                format!("{}{}", s, token.text.as_ref().unwrap())
+            }
+        });
+        let source = match &self.title {
+            Some(title) => format!("{}:{}", title, body),
+            None => body,
+        };
+        let header: Vec<String> = self.mode.iter()
+            .map(|mode| mode.name().to_string())
+            .chain(self.limits.and_then(|limits| limits.describe()))
+            .collect();
+        if header.is_empty() {
+            source
+        } else {
+            format!("({}) {}", header.join(", "), source)
+        }
+    }
+
+    // The title and everything that isn't a blank, lowercase, for searching
+    pub fn literal_text(&self) -> String {
+        let body = self.body.iter()
+            .filter(|token| token.pos.is_none())
+            .filter_map(|token| token.text.as_ref())
+            .map(|text| text.as_str());
+        self.title.iter().map(|title| title.as_str())
+            .chain(body)
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        normalize_tag(tag).map_or(false, |tag| self.tags.contains(&tag))
+    }
+
+    // the ones it didn't already have, kept sorted
+    pub fn add_tags(&mut self, tags: &[String]) {
+        for tag in tags.iter().filter_map(|tag| normalize_tag(tag)) {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self.tags.sort();
+    }
+
+    pub fn remove_tags(&mut self, tags: &[String]) {
+        let tags: Vec<String> = tags.iter().filter_map(|tag| normalize_tag(tag)).collect();
+        self.tags.retain(|tag| !tags.contains(tag));
+    }
+
+    // Short enough to list a bunch of them in one toot
+    pub fn summary(&self) -> String {
+        const MAX_SUMMARY_LENGTH: usize = 40;
+        let source = self.source();
+        let source = source.trim();
+        if source.len() <= MAX_SUMMARY_LENGTH {
+            return source.to_string();
+        }
+        let mut boundary = MAX_SUMMARY_LENGTH;
+        while !source.is_char_boundary(boundary) {
+            boundary -= 1;
+        }
+        format!("{}…", &source[..boundary])
+    }
+
+    // only counts blanks that haven't been filled in yet
     pub fn requirements(&self) -> HashMap<POS, usize> {
         let mut rv = HashMap::new();
         for token in &self.body {
+            if !token.is_placeholder {
+                continue;
+            }
             if let Some(pos) = token.pos {
                 rv.entry(pos)
                     .and_modify(|c| *c += 1)
@@ -176,6 +509,15 @@ impl Template {
 
     pub fn parse(status: &str) -> Result<Self, ParseError> {
         let status = sanitize_template(status);
+        // `(hybrid, one word each) ...` picks a mode and rules, anything else
+        // in () is just text
+        let ((mode, limits), status) = match Regex::new(r"^\s*\(([^)]*)\)\s*").unwrap().captures(&status) {
+            Some(header) => match parse_header(&header[1]) {
+                Some(parsed) => (parsed, status[header[0].len()..].to_string()),
+                None => ((None, None), status.clone()),
+            },
+            None => ((None, None), status.clone()),
+        };
 
         const OPEN: char = '[';
         const CLOSE: char = ']';
@@ -200,11 +542,17 @@ impl Template {
                         return Err(ParseError::MismatchedBracket);
                     }
                     in_brace = false;
-                    let pos = match str_to_pos(&chunk) {
+                    // [noun: a fruit] has a hint after the colon
+                    let (name, hint) = match chunk.find(END_TITLE) {
+                        Some(i) => (&chunk[..i], Some(chunk[i + 1..].trim().to_string())),
+                        None => (&chunk[..], None),
+                    };
+                    let pos = match str_to_pos(name.trim()) {
                         Some(p) => p,
-                        None => return Err(ParseError::UnknownPOS(chunk)),
+                        None => return Err(ParseError::UnknownPOS(name.to_string())),
                     };
-                    let token = Token::new_pos(pos);
+                    let mut token = Token::new_pos(pos);
+                    token.hint = hint.filter(|hint| hint != "");
                     body.push(token);
                     chunk = String::new();
                 },
@@ -237,10 +585,10 @@ impl Template {
                 }
             }
         }
-        Ok(Template {
-            title,
-            body,
-        })
+        let mut template = Template::new(title, body);
+        template.mode = mode;
+        template.limits = limits;
+        Ok(template)
     }
 
 }
@@ -269,7 +617,7 @@ impl std::error::Error for ParseError {
 
 #[cfg(test)]
 mod test {
-    use super::{POS, Token, Template, sanitize_source};
+    use super::{POS, Token, Template, sanitize_source, normalize_tag};
     #[test]
     fn sanity_templates() {
         let got = Template::parse(
@@ -312,11 +660,89 @@ mod test {
         assert_eq!(got.title, None);
     }
     #[test]
+    fn source_round_trips() {
+        let text = "the bowman: simple [noun]! [verbed] it";
+        let got = Template::parse(text).unwrap();
+        assert_eq!(got.source(), text);
+        assert_eq!(Template::parse(&got.source()).unwrap().body, got.body);
+    }
+    #[test]
+    fn hints_and_blanks() {
+        let mut got = Template::parse("t: a [noun: a fruit] and a [noun] [verbed]").unwrap();
+        assert_eq!(got.blanks()[0].hint, Some("a fruit".to_string()));
+        assert_eq!(got.blanks()[1].hint, None);
+        assert_eq!(got.source(), "t: a [noun: a fruit] and a [noun] [verbed]");
+        assert!(got.fill_blank(1, "pear".to_string()));
+        assert!(!got.fill_blank(1, "plum".to_string()));
+        assert!(!got.fill_blank(3, "plum".to_string()));
+        // still blank 2, even though 1 isn't a blank anymore
+        assert!(got.fill_blank(2, "ate".to_string()));
+        assert_eq!(got.free_blank(POS::Noun), Some(0));
+        assert_eq!(got.free_blank(POS::Verbed), None);
+        assert!(got.insert_placeholder(POS::Noun, "apple".to_string()));
+        assert_eq!(got.check_done().unwrap(), "t:\n a apple and a pear ate");
+    }
+    #[test]
+    fn modes() {
+        use super::Mode;
+        let got = Template::parse("(Hybrid) spooky: a [noun]").unwrap();
+        assert_eq!(got.mode, Some(Mode::Hybrid));
+        assert_eq!(got.title, Some("spooky".to_string()));
+        assert_eq!(got.source(), "(hybrid) spooky: a [noun]");
+        assert_eq!(Template::parse(&got.source()).unwrap().mode, Some(Mode::Hybrid));
+        let auto = Template::parse("(auto) spooky: a [noun]").unwrap();
+        assert_eq!(auto.mode(), Mode::Automatic);
+        assert_eq!(Template::parse("spooky: a [noun]").unwrap().mode(), Mode::Manual);
+        let aside = Template::parse("(not a mode) a [noun]").unwrap();
+        assert_eq!(aside.mode(), Mode::Automatic);
+        assert_eq!(aside.body[0], Token::new_str("(not a mode) a "));
+    }
+    #[test]
+    fn rules() {
+        use super::Mode;
+        use collection::Limits;
+        let got = Template::parse("(hybrid, one word each, wait 10 minutes) spooky: a [noun]").unwrap();
+        assert_eq!(got.mode, Some(Mode::Hybrid));
+        assert_eq!(got.limits, Some(Limits { max_words: None, one_word_each: true, cooldown_minutes: 10 }));
+        assert_eq!(got.source(), "(hybrid, one word each, wait 10 minutes before sending more) spooky: a [noun]");
+        assert_eq!(Template::parse(&got.source()).unwrap().limits, got.limits);
+        let rules_only = Template::parse("(up to 2 words each) spooky: a [noun]").unwrap();
+        assert_eq!((rules_only.mode, rules_only.limits.unwrap().max_words), (None, Some(2)));
+        // one part that isn't a mode or a rule and it's all just text
+        let aside = Template::parse("(hybrid, or not) a [noun]").unwrap();
+        assert_eq!((aside.mode, aside.limits), (None, None));
+    }
+    #[test]
+    fn duplicates() {
+        let plate = Template::parse("my [noun] [verbs] all the boys").unwrap();
+        let reformatted = Template::parse("My  [noun][verbs]\nall the BOYS").unwrap();
+        assert_eq!(plate.normalized(), reformatted.normalized());
+        let near = Template::parse("my [noun] [verbs] all the boys!!").unwrap();
+        assert_ne!(plate.normalized(), near.normalized());
+        assert!(plate.is_near_duplicate(&near));
+        let other_blanks = Template::parse("my [noun] [verbed] all the boys").unwrap();
+        assert!(!plate.is_near_duplicate(&other_blanks));
+        let other_words = Template::parse("your [noun] [verbs] none of the girls").unwrap();
+        assert!(!plate.is_near_duplicate(&other_words));
+    }
+    #[test]
     fn links_and_brackets() {
         let source = "https://stuffdotcom.com/stuff%20cool?thing=neat also <other stuff>";
         let got = sanitize_source(source);
         let exp = " also ";
         assert_eq!(got, exp);
     }
+    #[test]
+    fn tags() {
+        assert_eq!(normalize_tag("#Halloween!"), Some("halloween".to_string()));
+        assert_eq!(normalize_tag("#"), None);
+        let mut plate = Template::parse("a [noun]").unwrap();
+        plate.add_tags(&["Poetry".to_string(), "#halloween".to_string(), "poetry".to_string()]);
+        assert_eq!(plate.tags, vec!["halloween", "poetry"]);
+        assert!(plate.has_tag("#HALLOWEEN"));
+        plate.remove_tags(&["#halloween".to_string()]);
+        assert!(!plate.has_tag("halloween"));
+        assert_eq!(plate.tags, vec!["poetry"]);
+    }
 }
 